@@ -0,0 +1,122 @@
+use crate::types::Aggregation;
+
+/// Percentiles that are worth reporting in a summary.
+pub const SUMMARY_PERCENTILES: [f32; 4] = [5., 25., 75., 95.];
+
+/// Descriptive statistics over a set of samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub mean: f32,
+    pub median: f32,
+    pub std_dev: f32,
+    pub min: f32,
+    pub max: f32,
+    /// In the same order as `SUMMARY_PERCENTILES`.
+    pub percentiles: [f32; SUMMARY_PERCENTILES.len()],
+}
+
+pub fn mean(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.
+    }
+
+    samples.iter().sum::<f32>() / samples.len() as f32
+}
+
+/// The population standard deviation of the samples.
+pub fn std_dev(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.
+    }
+
+    let mean = mean(samples);
+
+    let variance = samples.iter()
+        .map(|sample| (sample - mean) * (sample - mean))
+        .sum::<f32>() / samples.len() as f32;
+
+    variance.sqrt()
+}
+
+/// `percent` should be between 0 and 100 inclusive. `sorted` must be sorted in ascending order.
+/// Interpolates linearly between the closest two samples.
+pub fn percentile(sorted: &[f32], percent: f32) -> f32 {
+    match sorted.len() {
+        0 => 0.,
+        1 => sorted[0],
+        len => {
+            let rank = (percent / 100.).clamp(0., 1.) * (len - 1) as f32;
+
+            let lower = rank.floor() as usize;
+            let upper = core::cmp::min(lower + 1, len - 1);
+
+            let t = rank - lower as f32;
+
+            sorted[lower] + (sorted[upper] - sorted[lower]) * t
+        }
+    }
+}
+
+pub fn sort(samples: &mut [f32]) {
+    samples.sort_by(|a, b| a.partial_cmp(b).expect("should have no NaNs"));
+}
+
+/// Combine the samples into a single value, as described by the `aggregation`.
+pub fn aggregate(aggregation: Aggregation, samples: &[f32]) -> f32 {
+    match aggregation {
+        Aggregation::Mean => mean(samples),
+        Aggregation::WorstCase => samples.iter().copied().fold(0., f32::max),
+        Aggregation::Percentile(percent) => {
+            let mut sorted = samples.to_vec();
+            sort(&mut sorted);
+
+            percentile(&sorted, percent)
+        },
+    }
+}
+
+pub fn summarize(samples: &[f32]) -> Summary {
+    let mut sorted = samples.to_vec();
+    sort(&mut sorted);
+
+    let mut percentiles = [0.; SUMMARY_PERCENTILES.len()];
+    for i in 0..SUMMARY_PERCENTILES.len() {
+        percentiles[i] = percentile(&sorted, SUMMARY_PERCENTILES[i]);
+    }
+
+    Summary {
+        mean: mean(&sorted),
+        median: percentile(&sorted, 50.),
+        std_dev: std_dev(&sorted),
+        min: sorted.first().copied().unwrap_or_default(),
+        max: sorted.last().copied().unwrap_or_default(),
+        percentiles,
+    }
+}
+
+#[cfg(test)]
+mod summarize_works {
+    use super::*;
+
+    #[test]
+    fn on_one_to_five() {
+        let summary = summarize(&[5., 3., 1., 4., 2.]);
+
+        assert_eq!(summary.mean, 3.);
+        assert_eq!(summary.median, 3.);
+        assert_eq!(summary.std_dev, 2.0f32.sqrt());
+        assert_eq!(summary.min, 1.);
+        assert_eq!(summary.max, 5.);
+        assert_eq!(summary.percentiles, [1.2, 2., 4., 4.8]);
+    }
+
+    #[test]
+    fn on_nothing() {
+        let summary = summarize(&[]);
+
+        assert_eq!(summary.mean, 0.);
+        assert_eq!(summary.median, 0.);
+        assert_eq!(summary.min, 0.);
+        assert_eq!(summary.max, 0.);
+    }
+}
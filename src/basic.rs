@@ -0,0 +1,1951 @@
+use std::io::Write;
+use crate::{output, svg};
+use crate::event_log::{self, LogEntry};
+use crate::event_source::{BuyAllBasedOnFullnessParams, Event, EventSourceBundle, MealSlot};
+use crate::xs::{self, Xs};
+use crate::types::{*, FoodTypes, food::{self, Grams}, Spec};
+
+#[derive(Clone, Debug)]
+pub struct Food {
+    key: food::Key,
+    option: food::Option,
+    grams: Grams,
+    /// How many days this has been on the shelf.
+    age: DayCount,
+    /// How many days it has been since this was opened, if it has been.
+    opened_age: Option<DayCount>,
+}
+
+impl Food {
+    pub fn key(&self) -> &food::Key {
+        &self.key
+    }
+
+    pub fn option(&self) -> &food::Option {
+        &self.option
+    }
+
+    /// How many grams are left.
+    pub fn grams(&self) -> Grams {
+        self.grams
+    }
+
+    /// How many days this has been on the shelf.
+    pub fn age(&self) -> DayCount {
+        self.age
+    }
+
+    /// How many days it has been since this was opened, if it has been.
+    pub fn opened_age(&self) -> Option<DayCount> {
+        self.opened_age
+    }
+
+    pub fn from_rng(food_types: &[food::Type], rng: &mut Xs) -> Self {
+        let index = xs::range(rng, 0..food_types.len() as u32) as usize;
+
+        let type_ = &food_types[index];
+
+        Self::from_rng_of_type(type_, rng)
+    }
+
+    pub fn from_rng_of_type(type_: &food::Type, rng: &mut Xs) -> Self {
+        let option_index = xs::range(rng, 0..type_.options.len() as u32) as usize;
+        let option = &type_.options[option_index];
+
+        Self::of_type(type_, option.clone())
+    }
+
+    pub fn of_type(type_: &food::Type, option: food::Option) -> Self {
+        Self::of_key(type_.key.clone(), option)
+    }
+
+    /// A full, unopened pack of the given option.
+    pub fn of_key(key: food::Key, option: food::Option) -> Self {
+        Self {
+            key,
+            grams: option.grams, // Full of the current grams
+            option,
+            age: 0,
+            opened_age: None,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        let unopened_expired = self.option.shelf_life
+            .is_some_and(|shelf_life| self.age >= shelf_life);
+
+        let opened_expired = match (self.opened_age, self.option.opened_shelf_life) {
+            (Some(opened_age), Some(opened_shelf_life)) => opened_age >= opened_shelf_life,
+            _ => false,
+        };
+
+        unopened_expired || opened_expired
+    }
+
+    pub fn current_fullness(
+        &self,
+        minimum_purchase_servings: food::Servings,
+        servings_per_pack: food::NonZeroServings,
+    ) -> f32 {
+        // Say minimum_purchase_servings is 7, and servings_per_pack is 4.
+        // We want to buy 2 servings because we need 2 4s to make at least 7.
+        // 7 / 4 = 1 (integer division) so we add one to make 2.
+        // this also works out with minimum_purchase_servings = 0.
+        let pack_count = (minimum_purchase_servings / servings_per_pack.get()) + 1;
+
+        let denominator = self.option.grams * pack_count;
+
+        self.grams as f32 / denominator as f32
+    }
+}
+
+pub type Performance = u32;
+
+/// A snapshot of the data needed to evaluate the performance metric(s) of the given set of events.
+/// That is, how well those events achieve some goal, not how long it took to simulate them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PerfSnapshot {
+    pub out_count: food::TotalGrams,
+    // 64k starvations ought to be enough for anybody!
+    pub starved_count: u16,
+    pub wasted_grams: food::TotalGrams,
+    pub spent: Money,
+}
+
+impl PerfSnapshot {
+    pub fn terms(&self, objective: &Objective) -> PerformanceTerms {
+        PerformanceTerms {
+            starved: self.starved_count as f32 * objective.starved_weight,
+            out: self.out_count as f32 * objective.out_weight,
+            waste: self.wasted_grams as f32 * objective.waste_weight,
+            spend: self.spent as f32 * objective.spend_weight,
+        }
+    }
+
+    /// The largest value of each field, between the two snapshots.
+    fn peak(self, other: Self) -> Self {
+        Self {
+            out_count: core::cmp::max(self.out_count, other.out_count),
+            starved_count: core::cmp::max(self.starved_count, other.starved_count),
+            wasted_grams: core::cmp::max(self.wasted_grams, other.wasted_grams),
+            spent: core::cmp::max(self.spent, other.spent),
+        }
+    }
+}
+
+/// The weighted contributions of each measured quantity to the overall performance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PerformanceTerms {
+    pub starved: f32,
+    pub out: f32,
+    pub waste: f32,
+    pub spend: f32,
+}
+
+impl PerformanceTerms {
+    pub fn performance(&self) -> Performance {
+        (self.starved + self.out + self.waste + self.spend) as Performance
+    }
+}
+
+/// Displays an amount of money in the smallest unit, as a decimal amount of the
+/// larger unit, assuming 100 small units per large unit, as is common.
+fn show_money(money: Money) -> String {
+    format!("{}.{:02}", money / 100, money % 100)
+}
+
+#[derive(Default)]
+struct Shelf {
+    shelf: Vec<Food>,
+    perf: PerfSnapshot,
+}
+
+#[derive(Debug)]
+enum TrackingStep {
+    Starved(Grams),
+    Ate { eaten: Grams, key: food::Key, out_count: Grams, servings_count: f32 },
+    Bought(Grams, food::Key, Money),
+    Spoiled(Grams, food::Key),
+    /// Some of the first key was wanted, but we had run out, so the second key was eaten instead.
+    Substituted { wanted: food::Key, with: food::Key },
+    /// All the ingredients were there, so they will each be eaten next.
+    MadeRecipe(String),
+    /// Nothing was eaten, because there was not enough of `key`, the first short ingredient.
+    /// A `Starved` step for the whole recipe follows.
+    RecipeIncomplete { recipe: String, key: food::Key, grams_short: Grams },
+    MealStarted(MealSlot),
+}
+
+/// How often one food type ran out, and what was eaten instead.
+#[derive(Clone, Debug, Default)]
+struct KeyOutReport {
+    out_count: u32,
+    grams_short: u32,
+    substituted_count: u32,
+    /// How many times each food type was eaten instead, in the same order as the food types.
+    substitutes: Vec<u32>,
+}
+
+/// One entry per food type, in the same order as the food types.
+struct OutReport(Vec<KeyOutReport>);
+
+impl OutReport {
+    fn new(food_types: &FoodTypes) -> Self {
+        Self(vec![
+            KeyOutReport {
+                substitutes: vec![0; food_types.len()],
+                ..KeyOutReport::default()
+            };
+            food_types.len()
+        ])
+    }
+
+    fn record(&mut self, food_types: &FoodTypes, step: &TrackingStep) {
+        let index_of = |key: &food::Key| food_types.iter().position(|food_type| &food_type.key == key);
+
+        match step {
+            TrackingStep::Ate { key, out_count, .. } if *out_count > 0 => {
+                if let Some(i) = index_of(key) {
+                    self.0[i].out_count += 1;
+                    self.0[i].grams_short += *out_count as u32;
+                }
+            },
+            TrackingStep::RecipeIncomplete { key, grams_short, .. } => {
+                if let Some(i) = index_of(key) {
+                    self.0[i].out_count += 1;
+                    self.0[i].grams_short += *grams_short as u32;
+                }
+            },
+            TrackingStep::Substituted { wanted, with } => {
+                if let (Some(i), Some(j)) = (index_of(wanted), index_of(with)) {
+                    self.0[i].substituted_count += 1;
+                    self.0[i].substitutes[j] += 1;
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod out_report_works {
+    use super::*;
+    use vec1::vec1;
+
+    #[test]
+    fn on_running_out_and_substituting() {
+        let food_type = |key: &str| food::Type::new(key, food::Option { grams: 1000, ..food::Option::default() });
+        let food_types: FoodTypes = vec1![food_type("Milk"), food_type("Yogurt")];
+
+        let mut report = OutReport::new(&food_types);
+
+        for step in [
+            TrackingStep::Ate { eaten: 250, key: "Milk".to_string(), out_count: 0, servings_count: 1. },
+            TrackingStep::Ate { eaten: 100, key: "Milk".to_string(), out_count: 150, servings_count: 0.4 },
+            TrackingStep::Substituted { wanted: "Milk".to_string(), with: "Yogurt".to_string() },
+            TrackingStep::Ate { eaten: 150, key: "Yogurt".to_string(), out_count: 0, servings_count: 1.5 },
+            TrackingStep::RecipeIncomplete {
+                recipe: "Smoothie".to_string(),
+                key: "Milk".to_string(),
+                grams_short: 200,
+            },
+        ] {
+            report.record(&food_types, &step);
+        }
+
+        let milk = &report.0[0];
+        assert_eq!(milk.out_count, 2);
+        assert_eq!(milk.grams_short, 350);
+        assert_eq!(milk.substituted_count, 1);
+        assert_eq!(milk.substitutes, vec![0, 1]);
+
+        let yogurt = &report.0[1];
+        assert_eq!(yogurt.out_count, 0);
+        assert_eq!(yogurt.substituted_count, 0);
+    }
+}
+
+/// How often one recipe was made, and what it was short of when it couldn't be.
+#[derive(Clone, Debug, Default)]
+struct RecipeReport {
+    made_count: u32,
+    incomplete_count: u32,
+    /// How many times each food type was the one that was short, in the same order as the food types.
+    short_of: Vec<u32>,
+}
+
+/// One entry per recipe, in the same order as the recipes.
+struct RecipeReports(Vec<RecipeReport>);
+
+impl RecipeReports {
+    fn new(recipes: &[food::Recipe], food_types: &FoodTypes) -> Self {
+        Self(vec![
+            RecipeReport {
+                short_of: vec![0; food_types.len()],
+                ..RecipeReport::default()
+            };
+            recipes.len()
+        ])
+    }
+
+    fn record(&mut self, recipes: &[food::Recipe], food_types: &FoodTypes, step: &TrackingStep) {
+        let index_of = |name: &String| recipes.iter().position(|recipe| &recipe.name == name);
+
+        match step {
+            TrackingStep::MadeRecipe(recipe) => {
+                if let Some(i) = index_of(recipe) {
+                    self.0[i].made_count += 1;
+                }
+            },
+            TrackingStep::RecipeIncomplete { recipe, key, .. } => {
+                if let Some(i) = index_of(recipe) {
+                    self.0[i].incomplete_count += 1;
+
+                    if let Some(j) = food_types.iter().position(|food_type| &food_type.key == key) {
+                        self.0[i].short_of[j] += 1;
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+fn calc_servings_count(
+    food_types: &FoodTypes,
+    key: &food::Key,
+    grams: Grams,
+) -> f32 {
+    food_types.iter().find(|type_| &type_.key == key)
+        .map(|type_| {
+            grams as f32 / type_.serving.get() as f32
+        })
+        .unwrap_or(-f32::INFINITY)
+}
+
+/// JSON has no infinities, so the servings of unknown food types are logged as `None`.
+fn logged_servings(servings_count: f32) -> Option<f32> {
+    servings_count.is_finite().then_some(servings_count)
+}
+
+fn log_entry(
+    food_types: &FoodTypes,
+    step: &TrackingStep,
+    day: u32,
+    event_index: u32,
+) -> LogEntry {
+    match step {
+        TrackingStep::Starved(grams) => LogEntry::Starved {
+            day,
+            event_index,
+            grams: *grams as u32,
+        },
+        TrackingStep::Ate { eaten, key, out_count, servings_count } => LogEntry::Ate {
+            day,
+            event_index,
+            key: key.clone(),
+            grams: *eaten as u32,
+            servings: logged_servings(*servings_count),
+            out_grams: *out_count as u32,
+        },
+        TrackingStep::Bought(grams, key, price) => LogEntry::Bought {
+            day,
+            event_index,
+            key: key.clone(),
+            grams: *grams as u32,
+            servings: logged_servings(calc_servings_count(food_types, key, *grams)),
+            price: *price,
+        },
+        TrackingStep::Spoiled(grams, key) => LogEntry::Spoiled {
+            day,
+            event_index,
+            key: key.clone(),
+            grams: *grams as u32,
+            servings: logged_servings(calc_servings_count(food_types, key, *grams)),
+        },
+        TrackingStep::Substituted { wanted, with } => LogEntry::Substituted {
+            day,
+            event_index,
+            key: wanted.clone(),
+            substitute: with.clone(),
+        },
+        TrackingStep::MadeRecipe(recipe) => LogEntry::MadeRecipe {
+            day,
+            event_index,
+            recipe: recipe.clone(),
+        },
+        TrackingStep::RecipeIncomplete { recipe, key, grams_short } => LogEntry::RecipeIncomplete {
+            day,
+            event_index,
+            recipe: recipe.clone(),
+            key: key.clone(),
+            grams_short: *grams_short as u32,
+        },
+        TrackingStep::MealStarted(slot) => LogEntry::MealStarted {
+            day,
+            event_index,
+            slot: *slot,
+        },
+    }
+}
+
+fn simulate(
+    rng: &mut Xs,
+    study: &mut Shelf,
+    tracking_steps: &mut Vec<TrackingStep>,
+    food_types: &FoodTypes,
+    substitution_policy: SubstitutionPolicy,
+    event: Event
+) {
+    macro_rules! calc_servings_per_pack {
+        ($food: expr, $serving: expr) => ({
+            let serving: food::NonZeroGrams = $serving;
+            let servings_per_pack: food::GramsSizedType = $food.option.grams / serving;
+            food::NonZeroServings::try_from(servings_per_pack).unwrap_or(food::NonZeroServings::MIN)
+        })
+    }
+
+    macro_rules! buy {
+        ($food: expr, $minimum_purchase_servings: expr) => {
+            let food: crate::basic::Food = $food;
+
+            // Buy more if one is below the configured minimum number of servings.
+            let mut serving = food::default_serving();
+            for type_ in food_types {
+                if type_.key == food.key {
+                    serving = type_.serving;
+                }
+            }
+
+            let servings_per_pack: food::NonZeroServings = calc_servings_per_pack!(food, serving);
+
+            let minimum_purchase_servings = $minimum_purchase_servings;
+
+            let mut servings_bought = 0;
+
+            while {
+                tracking_steps.push(TrackingStep::Bought(food.grams, food.key.clone(), food.option.price));
+                study.perf.spent += food.option.price;
+                // TODO? I guess we could probably do some math to eliminate the last
+                // clone? That would help in the common case of there being enough
+                // servings in one pack.
+                study.shelf.push(food.clone());
+
+                servings_bought += servings_per_pack.get();
+
+                servings_bought < minimum_purchase_servings
+            } {}
+        }
+    }
+
+    struct ShelfIndex(usize);
+
+    fn best_substitute_index(
+        study: &Shelf,
+        food_types: &FoodTypes,
+        substitution_policy: SubstitutionPolicy,
+        rng: &mut Xs,
+        key: &food::Key,
+        // TODO? offset param?
+    ) -> ShelfIndex {
+        // Past the end of the shelf, so eating it counts as starving.
+        let nothing_allowed = ShelfIndex(study.shelf.len());
+
+        let Some(target) = food_types.iter().find(|f| &f.key == key) else {
+            return match substitution_policy {
+                SubstitutionPolicy::ServingSize => ShelfIndex(0),
+                SubstitutionPolicy::SameCategory
+                | SubstitutionPolicy::Ranked
+                | SubstitutionPolicy::Random => nothing_allowed,
+            };
+        };
+
+        match substitution_policy {
+            SubstitutionPolicy::ServingSize
+            | SubstitutionPolicy::SameCategory => {
+                // Favour items with a similar serving size, as a hueristic for similarity.
+                let target_serving_size = target.serving;
+
+                let mut best_index = match substitution_policy {
+                    SubstitutionPolicy::SameCategory => nothing_allowed.0,
+                    _ => 0,
+                };
+                let mut best_difference = Grams::MAX;
+
+                for i in 0..study.shelf.len() {
+                    let candidate_key = &study.shelf[i].key;
+
+                    for food in food_types {
+                        if candidate_key == &food.key && &food.key != key {
+                            if substitution_policy == SubstitutionPolicy::SameCategory
+                            && !food.categories.iter().any(|c| target.categories.contains(c)) {
+                                break
+                            }
+
+                            let serving_size = food.serving;
+
+                            let difference = target_serving_size.get().abs_diff(serving_size.get());
+
+                            if difference < best_difference {
+                                best_difference = difference;
+                                best_index = i;
+                            }
+
+                            break
+                        }
+                    }
+                }
+
+                ShelfIndex(best_index)
+            },
+            SubstitutionPolicy::Ranked => {
+                target.substitutes.iter()
+                    .find_map(|substitute| study.shelf.iter().position(|f| &f.key == substitute))
+                    .map(ShelfIndex)
+                    .unwrap_or(nothing_allowed)
+            },
+            SubstitutionPolicy::Random => {
+                let candidates = (0..study.shelf.len())
+                    .filter(|&i| &study.shelf[i].key != key)
+                    .collect::<Vec<_>>();
+
+                if candidates.is_empty() {
+                    return nothing_allowed
+                }
+
+                ShelfIndex(candidates[xs::range(rng, 0..candidates.len() as u32) as usize])
+            },
+        }
+    }
+
+    fn eat_at(
+        study: &mut Shelf,
+        tracking_steps: &mut Vec<TrackingStep>,
+        index: ShelfIndex,
+        grams: Grams,
+        food_types: &FoodTypes,
+        substitution_policy: SubstitutionPolicy,
+        rng: &mut Xs,
+    ) {
+        if index.0 >= study.shelf.len() {
+            study.perf.starved_count += 1;
+            tracking_steps.push(TrackingStep::Starved(grams));
+            return
+        }
+        let food = &mut study.shelf[index.0];
+        if let Some(subtracted) = food.grams.checked_sub(grams) {
+            // Base case
+            food.grams = subtracted;
+            food.opened_age.get_or_insert(0);
+            tracking_steps.push(TrackingStep::Ate {
+                eaten: grams,
+                key: food.key.clone(),
+                out_count: 0,
+                servings_count: calc_servings_count(food_types, &food.key, grams),
+            });
+        } else {
+            let remaining_grams = grams - food.grams;
+            let food = study.shelf.swap_remove(index.0);
+
+            // Go check for more of the same thing
+            if let Some(new_index) = study.shelf.iter().position(|f| f.key == food.key) {
+                // TODO? track recursion depth so display can indent?
+                tracking_steps.push(TrackingStep::Ate {
+                    eaten: food.grams,
+                    key: food.key.clone(),
+                    out_count: 0,
+                    servings_count: calc_servings_count(food_types, &food.key, food.grams),
+                });
+
+                eat_at(study, tracking_steps, ShelfIndex(new_index), remaining_grams, food_types, substitution_policy, rng);
+
+                return
+            }
+
+            // Ran out; pick an alternate
+            tracking_steps.push(TrackingStep::Ate {
+                eaten: food.grams,
+                key: food.key.clone(),
+                out_count: remaining_grams,
+                servings_count: calc_servings_count(food_types, &food.key, food.grams),
+            });
+
+            study.perf.out_count += remaining_grams as u32;
+
+            let substitute_index = best_substitute_index(study, food_types, substitution_policy, rng, &food.key);
+
+            if let Some(substitute) = study.shelf.get(substitute_index.0) {
+                tracking_steps.push(TrackingStep::Substituted {
+                    wanted: food.key.clone(),
+                    with: substitute.key.clone(),
+                });
+            }
+
+            eat_at(study, tracking_steps, substitute_index, remaining_grams, food_types, substitution_policy, rng);
+        }
+    }
+
+    match event {
+        Event::Ate(key, grams, .. ) => {
+            if let Some(index) = study.shelf.iter().position(|f| f.key == key) {
+                eat_at(study, tracking_steps, ShelfIndex(index), grams, food_types, substitution_policy, rng);
+            } else {
+                tracking_steps.push(TrackingStep::Ate {
+                    eaten: 0,
+                    key: key.clone(),
+                    out_count: grams,
+                    servings_count: 0.0,
+                });
+
+                study.perf.out_count += grams as u32;
+
+                let substitute_index = best_substitute_index(study, food_types, substitution_policy, rng, &key);
+
+                if let Some(substitute) = study.shelf.get(substitute_index.0) {
+                    tracking_steps.push(TrackingStep::Substituted {
+                        wanted: key.clone(),
+                        with: substitute.key.clone(),
+                    });
+                }
+
+                eat_at(study, tracking_steps, substitute_index, grams, food_types, substitution_policy, rng);
+            }
+        },
+        Event::AteRecipe(food::Recipe { name, ingredients }) => {
+            // Check that everything is there first, so either all of it is eaten, or none of it is.
+            let short = ingredients.iter().find_map(|(key, grams)| {
+                let on_shelf: u32 = study.shelf.iter()
+                    .filter(|f| &f.key == key)
+                    .map(|f| f.grams as u32)
+                    .sum();
+
+                (on_shelf < *grams as u32).then(|| (key.clone(), grams - on_shelf as Grams))
+            });
+
+            match short {
+                Some((key, grams_short)) => {
+                    study.perf.out_count += grams_short as u32;
+
+                    tracking_steps.push(TrackingStep::RecipeIncomplete {
+                        recipe: name,
+                        key,
+                        grams_short,
+                    });
+
+                    // Nothing else is eaten in its place, so the whole meal is missed.
+                    let recipe_grams = ingredients.iter()
+                        .fold(0 as Grams, |sum, (_, grams)| sum.saturating_add(*grams));
+
+                    study.perf.starved_count += 1;
+                    tracking_steps.push(TrackingStep::Starved(recipe_grams));
+                },
+                None => {
+                    tracking_steps.push(TrackingStep::MadeRecipe(name));
+
+                    for (key, grams) in ingredients {
+                        if let Some(index) = study.shelf.iter().position(|f| f.key == key) {
+                            eat_at(study, tracking_steps, ShelfIndex(index), grams, food_types, substitution_policy, rng);
+                        }
+                    }
+                },
+            }
+        },
+        Event::Bought(food, minimum_purchase_servings) => {
+            buy!(food, minimum_purchase_servings);
+        }
+        Event::MealStarted(slot) => {
+            tracking_steps.push(TrackingStep::MealStarted(slot));
+        }
+        Event::BuyAllBasedOnFullness(
+            BuyAllBasedOnFullnessParams {
+                max_count,
+                offset,
+                fullness_threshold,
+                minimum_purchase_servings,
+            }
+        ) => {
+            // buy one of each kind of food if there isn't a more than fullness_threshold full version of it there.
+            let mut count = 0;
+
+            for type_ in food_types.iter() {
+                let mut total_fullness = 0.;
+
+                // TODO? avoid O(n^2) here? Like maybe calcualting all the totals AOT say?
+                for i in 0..study.shelf.len() {
+                    let food = &study.shelf[(i + offset) % study.shelf.len()];
+                    if type_.key == food.key {
+                        let servings_per_pack: food::NonZeroServings = calc_servings_per_pack!(food, type_.serving);
+
+                        total_fullness += food.current_fullness(minimum_purchase_servings, servings_per_pack);
+                        break
+                    }
+                }
+
+                if total_fullness < fullness_threshold && count < max_count {
+                    buy!(Food::from_rng_of_type(type_, rng), minimum_purchase_servings);
+                    count += 1;
+                }
+            }
+        }
+        Event::DayPassed => {
+            for food in study.shelf.iter_mut() {
+                food.age = food.age.saturating_add(1);
+                if let Some(opened_age) = &mut food.opened_age {
+                    *opened_age = opened_age.saturating_add(1);
+                }
+            }
+
+            let mut i = 0;
+            while i < study.shelf.len() {
+                if study.shelf[i].is_expired() {
+                    let food = study.shelf.remove(i);
+
+                    // An empty pack going off wastes nothing, so it is just thrown out.
+                    if food.grams > 0 {
+                        study.perf.wasted_grams += food.grams as u32;
+                        tracking_steps.push(TrackingStep::Spoiled(food.grams, food.key));
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub snapshot: PerfSnapshot,
+    pub total_items: usize,
+    pub total_grams: food::TotalGrams,
+}
+
+/// How much of one food type is on the shelf.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyStock {
+    pub grams: u32,
+    pub items: u32,
+}
+
+/// One entry per food type, in the same order as the food types.
+fn stock_by_key(shelf: &Shelf, food_types: &FoodTypes) -> Vec<KeyStock> {
+    let mut output = vec![KeyStock::default(); food_types.len()];
+
+    for food in shelf.shelf.iter() {
+        if let Some(i) = food_types.iter().position(|food_type| food_type.key == food.key) {
+            output[i].grams += food.grams as u32;
+            output[i].items += 1;
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod stock_by_key_works {
+    use super::*;
+    use vec1::vec1;
+
+    #[test]
+    fn on_several_items_of_a_type() {
+        let food_type = |key: &str| food::Type::new(key, food::Option { grams: 500, ..food::Option::default() });
+        let food_types: FoodTypes = vec1![food_type("Bread"), food_type("Jam"), food_type("Milk")];
+
+        let mut study = Shelf::default();
+        for (i, grams) in [(0, 500), (1, 200), (0, 100)] {
+            let mut food = Food::of_type(&food_types[i], food_types[i].options[0].clone());
+            food.grams = grams;
+            study.shelf.push(food);
+        }
+        // Not one of the food types, so not counted anywhere.
+        study.shelf.push(Food::of_key("Mystery Meat".to_string(), food::Option { grams: 300, ..food::Option::default() }));
+
+        assert_eq!(
+            stock_by_key(&study, &food_types),
+            vec![
+                KeyStock { grams: 600, items: 2 },
+                KeyStock { grams: 200, items: 1 },
+                KeyStock { grams: 0, items: 0 },
+            ],
+        );
+    }
+}
+
+fn stats(shelf: &Shelf) -> Stats {
+    let mut stats = Stats {
+        snapshot: shelf.perf,
+        ..Stats::default()
+    };
+
+    for food in shelf.shelf.iter() {
+        stats.total_grams += food.grams as u32;
+    }
+
+    stats.total_items = shelf.shelf.len();
+
+    stats
+}
+
+#[derive(Clone, Debug)]
+enum EventEntry {
+    InitialDayMarker,
+    DayMarker,
+    Event(Event),
+}
+
+/// Turns a recorded event log back into events.
+/// Steps that are consequences of other steps, like substitutions and spoilage, are skipped,
+/// since they will happen again as the events are simulated. For each event index, the first
+/// `Ate` entry is what was wanted; the amount wanted is the grams eaten plus the grams short of
+/// the `Ate` entries of that key before any `Substituted` entry. So eating from several packs,
+/// or running out, replays as wanting the whole amount again. Recipes replay as wanting the whole
+/// recipe again, ignoring the `Ate` entries for their ingredients.
+fn replay_events(
+    entries: &[LogEntry],
+    food_types: &FoodTypes,
+    recipes: &[food::Recipe],
+) -> Vec<EventEntry> {
+    let mut events = Vec::with_capacity(entries.len());
+
+    let has_day_start = entries.iter().any(|entry| matches!(entry, LogEntry::DayStart { .. }));
+    if !has_day_start {
+        events.push(EventEntry::InitialDayMarker);
+    }
+
+    // The day that the next `DayMarker` will end.
+    let mut current_day = 0;
+
+    struct Wanted {
+        event_index: u32,
+        key: food::Key,
+        grams: u32,
+        substituted: bool,
+    }
+
+    let mut wanted: Option<Wanted> = None;
+
+    // The event index of the last recipe, whose ingredients should not be replayed on their own.
+    let mut recipe_event_index = None;
+
+    macro_rules! flush_wanted {
+        () => {
+            if let Some(Wanted { key, grams, .. }) = wanted.take() {
+                events.push(EventEntry::Event(Event::Ate(key, grams.try_into().unwrap_or(Grams::MAX))));
+            }
+        }
+    }
+
+    macro_rules! advance_to {
+        ($day: expr) => {
+            while current_day < $day {
+                flush_wanted!();
+                events.push(EventEntry::DayMarker);
+                current_day += 1;
+            }
+        }
+    }
+
+    for entry in entries {
+        match entry {
+            LogEntry::DayStart { .. } => {
+                flush_wanted!();
+                if !events.iter().any(|event| matches!(event, EventEntry::InitialDayMarker)) {
+                    events.push(EventEntry::InitialDayMarker);
+                }
+            },
+            LogEntry::DayEnd { day, .. } => {
+                advance_to!(day + 1);
+            },
+            &LogEntry::Ate { day, event_index, .. } if recipe_event_index == Some(event_index) => {
+                advance_to!(day);
+            },
+            &LogEntry::Ate { day, event_index, ref key, grams, out_grams, .. } => {
+                advance_to!(day);
+
+                match &mut wanted {
+                    Some(w) if w.event_index == event_index => {
+                        if !w.substituted && &w.key == key {
+                            w.grams += grams + out_grams;
+                        }
+                    },
+                    _ => {
+                        flush_wanted!();
+                        wanted = Some(Wanted {
+                            event_index,
+                            key: key.clone(),
+                            grams: grams + out_grams,
+                            substituted: false,
+                        });
+                    },
+                }
+            },
+            &LogEntry::Substituted { event_index, .. } => {
+                if let Some(w) = &mut wanted && w.event_index == event_index {
+                    w.substituted = true;
+                }
+            },
+            &LogEntry::Bought { day, ref key, grams, price, .. } => {
+                advance_to!(day);
+                flush_wanted!();
+
+                let grams = grams.try_into().unwrap_or(Grams::MAX);
+
+                let option = food_types.iter()
+                    .find(|type_| &type_.key == key)
+                    .map(|type_| {
+                        type_.options.iter()
+                            .find(|option| option.grams == grams)
+                            .unwrap_or(type_.options.first())
+                            .clone()
+                    })
+                    .unwrap_or_default();
+
+                events.push(EventEntry::Event(Event::Bought(
+                    Food::of_key(
+                        key.clone(),
+                        food::Option {
+                            grams,
+                            price,
+                            ..option
+                        },
+                    ),
+                    0,
+                )));
+            },
+            &LogEntry::MadeRecipe { day, event_index, ref recipe }
+            | &LogEntry::RecipeIncomplete { day, event_index, ref recipe, .. } => {
+                advance_to!(day);
+                flush_wanted!();
+
+                if let Some(recipe) = recipes.iter().find(|r| &r.name == recipe) {
+                    events.push(EventEntry::Event(Event::AteRecipe(recipe.clone())));
+                }
+
+                recipe_event_index = Some(event_index);
+            },
+            &LogEntry::MealStarted { day, slot, .. } => {
+                advance_to!(day);
+                flush_wanted!();
+
+                events.push(EventEntry::Event(Event::MealStarted(slot)));
+            },
+            LogEntry::Stock { .. }
+            | LogEntry::Starved { .. }
+            | LogEntry::Spoiled { .. } => {},
+        }
+    }
+
+    flush_wanted!();
+
+    // End the last day, if the log didn't.
+    if !matches!(events.last(), Some(EventEntry::DayMarker)) {
+        events.push(EventEntry::DayMarker);
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod replay_events_works {
+    use super::*;
+    use vec1::vec1;
+
+    fn describe(events: &[EventEntry]) -> Vec<String> {
+        events.iter().map(|event| match event {
+            EventEntry::InitialDayMarker => "Start".to_string(),
+            EventEntry::DayMarker => "End".to_string(),
+            EventEntry::Event(Event::Ate(key, grams)) => format!("Ate {grams}g of {key}"),
+            EventEntry::Event(Event::Bought(food, _)) => format!("Bought {}g of {}", food.grams(), food.key()),
+            EventEntry::Event(Event::AteRecipe(recipe)) => format!("Made {}", recipe.name),
+            EventEntry::Event(other) => format!("{other:?}"),
+        }).collect()
+    }
+
+    #[test]
+    fn on_running_out_substituting_and_recipes() {
+        let food_type = |key: &str| food::Type::new(key, food::Option { grams: 1000, ..food::Option::default() });
+        let food_types: FoodTypes = vec1![food_type("Milk"), food_type("Yogurt"), food_type("Cheerios")];
+        let recipes = vec![food::Recipe {
+            name: "Cereal".to_string(),
+            ingredients: vec1![("Cheerios".to_string(), 40), ("Milk".to_string(), 250)],
+        }];
+
+        let ate = |day, event_index, key: &str, grams, out_grams| LogEntry::Ate {
+            day,
+            event_index,
+            key: key.to_string(),
+            grams,
+            servings: None,
+            out_grams,
+        };
+
+        let entries = [
+            LogEntry::DayStart { day: 0, event_index: 0 },
+            LogEntry::Bought { day: 0, event_index: 1, key: "Milk".to_string(), grams: 1000, servings: None, price: 0 },
+            // Eating from two packs, then running out, then eating something else instead, is
+            // all from wanting 250g of milk.
+            ate(0, 2, "Milk", 100, 0),
+            ate(0, 2, "Milk", 50, 100),
+            LogEntry::Substituted { day: 0, event_index: 2, key: "Milk".to_string(), substitute: "Yogurt".to_string() },
+            ate(0, 2, "Yogurt", 100, 0),
+            ate(0, 3, "Yogurt", 100, 0),
+            LogEntry::DayEnd { day: 0, event_index: 4 },
+            LogEntry::DayStart { day: 1, event_index: 4 },
+            // The ingredients are eaten again as part of the recipe.
+            LogEntry::MadeRecipe { day: 1, event_index: 5, recipe: "Cereal".to_string() },
+            ate(1, 5, "Cheerios", 40, 0),
+            ate(1, 5, "Milk", 250, 0),
+            LogEntry::DayEnd { day: 1, event_index: 6 },
+        ];
+
+        assert_eq!(
+            describe(&replay_events(&entries, &food_types, &recipes)),
+            vec![
+                "Start",
+                "Bought 1000g of Milk",
+                "Ate 250g of Milk",
+                "Ate 100g of Yogurt",
+                "End",
+                "Made Cereal",
+                "End",
+            ],
+        );
+    }
+}
+
+/// Generates all the events up front, so that the random history does not depend on how the
+/// simulation is stepped through.
+fn generate_events(spec: &Spec, extras: &BasicExtras, rng: &mut Xs) -> Vec<EventEntry> {
+    let BasicExtras {
+        mode,
+        food_types,
+        initial_event_source_specs,
+        repeated_event_source_specs,
+        objective: _,
+        recipes,
+        substitution_policy: _,
+    } = extras;
+
+    let day_count_min = spec.day_count_min as u32;
+    let day_count_one_past_max = spec.day_count_one_past_max as u32;
+
+    let day_count = xs::range(rng, day_count_min..day_count_one_past_max) as usize;
+
+    type Events = Vec<EventEntry>;
+
+    let mut events: Events = Vec::with_capacity(day_count);
+
+    macro_rules! emit {
+        ($source: expr) => ({
+            let mut start_index = events.len().saturating_sub(1);
+
+            let mut day_marker_count = 0;
+
+            while start_index > 0 && day_marker_count < 3 {
+                if matches!(
+                    events[start_index],
+                    EventEntry::InitialDayMarker | EventEntry::DayMarker,
+                ) {
+                    day_marker_count += 1;
+                }
+                start_index -= 1;
+            }
+
+            let yesterday_slice = &events[start_index..];
+
+            let mut recently_eaten_foods = Vec::with_capacity(yesterday_slice.len() / 2);
+
+            for event_entry in yesterday_slice {
+                match event_entry {
+                    EventEntry::Event(Event::Ate(key, _)) => {
+                        recently_eaten_foods.push(key.to_owned());
+                    }
+                    EventEntry::Event(Event::AteRecipe(recipe)) => {
+                        for (key, _) in recipe.ingredients.iter() {
+                            recently_eaten_foods.push(key.to_owned());
+                        }
+                    }
+                    EventEntry::Event(Event::Bought(..))
+                    | EventEntry::Event(Event::BuyAllBasedOnFullness(..))
+                    | EventEntry::Event(Event::MealStarted(..))
+                    | EventEntry::Event(Event::DayPassed) => {}
+                    EventEntry::InitialDayMarker | EventEntry::DayMarker => {
+                        break
+                    }
+                }
+            }
+
+            $source.emit(EventSourceBundle {
+                push_event: &mut |e| events.push(EventEntry::Event(e)),
+                rng: &mut *rng,
+                food_types,
+                recipes,
+                recently_eaten_foods,
+            });
+        })
+    }
+
+    macro_rules! get_events {
+        ($es_specs: expr, $i: expr) => {
+            let i = $i;
+            let chunk_index = i / 7;
+            let bit_index = i % 7;
+
+            for es_spec in $es_specs.iter() {
+                let happens_today = {
+                    // TODO? Avoid needing to loop over the same chunks each day?
+                    //       Or is nth on cycle already optimized?
+                    match es_spec.recurrence.iter().cycle().nth(chunk_index) {
+                        // Must be an empty list. That means always.
+                        None => { true },
+                        Some(chunk) => { ((chunk >> bit_index) & 1) == 1 },
+                    }
+                };
+
+                if happens_today {
+                    emit!(es_spec.source);
+                }
+            }
+        }
+    }
+
+    match mode {
+        BasicMode::Replay(ReplaySpec { entries }) => {
+            events = replay_events(entries, food_types, recipes);
+        },
+        BasicMode::Run
+        | BasicMode::Search(..)
+        | BasicMode::PrintCalls(..)
+        | BasicMode::Batch(..) => {
+            get_events!(initial_event_source_specs, 0);
+
+            events.push(EventEntry::InitialDayMarker);
+
+            for i in 0..day_count {
+                get_events!(repeated_event_source_specs, i);
+
+                events.push(EventEntry::DayMarker);
+            }
+            assert!(events.len() > food_types.len());
+        },
+    }
+
+    events
+}
+
+/// The totals shown at the end of each day in the step by step output.
+#[derive(Clone, Copy, Debug, Default)]
+struct DailyTotals {
+    ate: food::TotalGrams,
+    bought: food::TotalGrams,
+    spoiled: food::TotalGrams,
+    spent: Money,
+}
+
+pub struct RunOutput {
+    pub performance: Performance,
+    pub terms: PerformanceTerms,
+    pub out_count: food::TotalGrams,
+    pub starved_count: u16,
+}
+
+/// A single run of the Basic model, that can be stepped through a day at a time.
+/// All the events are generated when this is created, so the results are the same however it is
+/// stepped through. Output enabled by the spec, like the step by step output, is written to `W`.
+pub struct Simulation<'spec, W: Write = std::io::Sink> {
+    spec: &'spec Spec,
+    food_types: &'spec FoodTypes,
+    recipes: &'spec [food::Recipe],
+    substitution_policy: SubstitutionPolicy,
+    objective: &'spec Objective,
+    w: W,
+    rng: Xs,
+    study: Shelf,
+    events: Vec<EventEntry>,
+    next_event_index: usize,
+    all_stats: Vec<Stats>,
+    // The length of `all_stats` at the end of each day.
+    day_boundaries: Vec<usize>,
+    // The stock of each food type at the start of the first day, then at the end of each day.
+    daily_stock: Vec<Vec<KeyStock>>,
+    tracking_steps: Vec<TrackingStep>,
+    out_report: OutReport,
+    recipe_reports: RecipeReports,
+    day_number: u32,
+    daily_totals: DailyTotals,
+    event_log: Option<std::io::BufWriter<std::fs::File>>,
+}
+
+pub fn run(spec: &Spec, w: impl Write) -> Result<RunOutput, std::io::Error> {
+    Simulation::with_output(spec, w)?.finish()
+}
+
+impl <'spec> Simulation<'spec> {
+    /// A simulation that discards any output.
+    pub fn new(spec: &'spec Spec) -> Result<Self, std::io::Error> {
+        Self::with_output(spec, std::io::sink())
+    }
+}
+
+impl <'spec, W: Write> Simulation<'spec, W> {
+    pub fn with_output(spec: &'spec Spec, w: W) -> Result<Self, std::io::Error> {
+        let extras = match &spec.mode {
+            crate::Mode::Basic(extras) => {
+                extras
+            },
+            crate::Mode::Minimal => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Simulation requires a spec in Basic mode",
+                ));
+            }
+        };
+
+        let mut rng = xs::from_seed(spec.seed.unwrap_or_default());
+
+        let events = generate_events(spec, extras, &mut rng);
+
+        let day_count = events.iter().filter(|e| matches!(e, EventEntry::DayMarker)).count();
+
+        let event_log = match &spec.event_log_path {
+            Some(path) => Some(std::io::BufWriter::new(std::fs::File::create(path)?)),
+            None => None,
+        };
+
+        Ok(Self {
+            spec,
+            food_types: &extras.food_types,
+            recipes: &extras.recipes,
+            substitution_policy: extras.substitution_policy,
+            objective: &extras.objective,
+            w,
+            rng,
+            study: Shelf::default(),
+            all_stats: Vec::with_capacity(events.len() + 1),
+            day_boundaries: Vec::with_capacity(day_count),
+            daily_stock: Vec::with_capacity(day_count + 1),
+            tracking_steps: Vec::with_capacity(16),
+            out_report: OutReport::new(&extras.food_types),
+            recipe_reports: RecipeReports::new(&extras.recipes, &extras.food_types),
+            day_number: 0,
+            daily_totals: DailyTotals::default(),
+            event_log,
+            events,
+            next_event_index: 0,
+        })
+    }
+
+    /// Whether all the events have been simulated.
+    pub fn is_finished(&self) -> bool {
+        self.next_event_index >= self.events.len()
+    }
+
+    /// The number of days that have ended so far.
+    pub fn day(&self) -> u32 {
+        self.day_number
+    }
+
+    /// How many days will have been simulated once finished.
+    pub fn day_count(&self) -> usize {
+        self.events.iter().filter(|e| matches!(e, EventEntry::DayMarker)).count()
+    }
+
+    pub fn shelf(&self) -> &[Food] {
+        &self.study.shelf
+    }
+
+    /// The current totals that make up the performance.
+    pub fn perf(&self) -> PerfSnapshot {
+        self.study.perf
+    }
+
+    /// The stats from just before each event simulated so far.
+    pub fn stats(&self) -> &[Stats] {
+        &self.all_stats
+    }
+
+    /// The stock of each food type, in the same order as the food types, at the start of the
+    /// first day, then at the end of each day so far.
+    pub fn daily_stock(&self) -> &[Vec<KeyStock>] {
+        &self.daily_stock
+    }
+
+    /// Simulates events until the end of the next day. Returns whether a day was ended, which
+    /// is false if the simulation was already finished.
+    pub fn step_day(&mut self) -> Result<bool, std::io::Error> {
+        while let Some(event_entry) = self.events.get(self.next_event_index).cloned() {
+            let i = self.next_event_index;
+            self.next_event_index += 1;
+
+            let is_day_marker = matches!(event_entry, EventEntry::DayMarker);
+
+            self.step_event(i, event_entry)?;
+
+            if is_day_marker {
+                return Ok(true)
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Simulates any remaining events, then writes out everything requested in the spec, and
+    /// computes the final performance.
+    pub fn finish(mut self) -> Result<RunOutput, std::io::Error> {
+        while self.step_day()? {}
+
+        let Self {
+            spec,
+            food_types,
+            recipes,
+            objective,
+            mut w,
+            study,
+            all_stats,
+            day_boundaries,
+            daily_stock,
+            out_report,
+            recipe_reports,
+            event_log,
+            ..
+        } = self;
+
+        if let Some(mut event_log) = event_log {
+            event_log.flush()?;
+        }
+
+        if spec.show_step_by_step {
+            writeln!(w)?;
+        }
+
+        if spec.show_grams {
+            writeln!(w, "grams: [")?;
+            for stats in &all_stats {
+                writeln!(w, "    {}", stats.total_grams)?;
+            }
+            writeln!(w, "]")?;
+
+            writeln!(w)?;
+        }
+
+        if spec.show_items {
+            writeln!(w, "items: [")?;
+            for stats in &all_stats {
+                writeln!(w, "    {}", stats.total_items)?;
+            }
+            writeln!(w, "]")?;
+
+            writeln!(w)?;
+        }
+        if let Some(path) = &spec.stock_chart_path {
+            let x_markers: Vec<f32> = day_boundaries.iter().map(|&i| i as f32).collect();
+
+            let chart = |title: &str, y_label: &str, y: &dyn Fn(&Stats) -> f32| svg::Chart {
+                title: title.to_string(),
+                x_label: "event".to_string(),
+                y_label: y_label.to_string(),
+                series: vec![svg::Series {
+                    label: "total".to_string(),
+                    points: all_stats.iter()
+                        .enumerate()
+                        .map(|(i, stats)| (i as f32, y(stats)))
+                        .collect(),
+                }],
+                x_markers: x_markers.clone(),
+            };
+
+            svg::write_charts(
+                &[
+                    chart("Stock (grams)", "grams", &|stats| stats.total_grams as f32),
+                    chart("Stock (items)", "items", &|stats| stats.total_items as f32),
+                ],
+                std::io::BufWriter::new(std::fs::File::create(path)?),
+            )?;
+        }
+
+        if let Some(path) = &spec.stock_series_path {
+            let mut rows = Vec::with_capacity(daily_stock.len() * food_types.len());
+
+            for (day, stock) in daily_stock.iter().enumerate() {
+                for (food_type, &KeyStock { grams, items }) in food_types.iter().zip(stock) {
+                    rows.push(output::StockRow {
+                        day: day as u32,
+                        key: food_type.key.clone(),
+                        grams,
+                        items,
+                    });
+                }
+            }
+
+            output::write_stock_series(
+                spec.stock_series_format,
+                &rows,
+                std::io::BufWriter::new(std::fs::File::create(path)?),
+            )?;
+        }
+
+        // The stats are from before each event, so the last event, and the end of the last day,
+        // are only counted in the final snapshot.
+        let mut peak = study.perf;
+
+        for stats in &all_stats {
+            peak = peak.peak(stats.snapshot);
+        }
+
+        let PerfSnapshot {
+            out_count,
+            starved_count,
+            wasted_grams,
+            spent,
+        } = peak;
+
+        let terms = peak.terms(objective);
+        let performance = terms.performance();
+
+        if !spec.hide_summary {
+            writeln!(w, "out_count (closer to 0 is better): {out_count}")?;
+            writeln!(w, "starved_count (closer to 0 is better): {starved_count}")?;
+            writeln!(w, "wasted_grams (closer to 0 is better): {wasted_grams}")?;
+            writeln!(w, "spent (closer to 0 is better): {}\n", show_money(spent))?;
+            writeln!(w, "performance terms:")?;
+            writeln!(w, "    starved: {starved_count} * {} = {}", objective.starved_weight, terms.starved)?;
+            writeln!(w, "    out: {out_count} * {} = {}", objective.out_weight, terms.out)?;
+            writeln!(w, "    waste: {wasted_grams} * {} = {}", objective.waste_weight, terms.waste)?;
+            writeln!(w, "    spend: {} * {} = {}\n", show_money(spent), objective.spend_weight, terms.spend)?;
+            writeln!(w, "performance (closer to 0 is better): {performance},")?;
+
+            writeln!(w, "\nran out, by food:")?;
+            for (food_type, report) in food_types.iter().zip(&out_report.0) {
+                let KeyOutReport { out_count, grams_short, substituted_count, substitutes } = report;
+
+                write!(
+                    w,
+                    "    {}: ran out {out_count} times, {grams_short}g short, substituted {substituted_count} times",
+                    food_type.key,
+                )?;
+
+                let mut separator = " (";
+                for (substitute_type, count) in food_types.iter().zip(substitutes) {
+                    if *count > 0 {
+                        write!(w, "{separator}{}: {count}", substitute_type.key)?;
+                        separator = ", ";
+                    }
+                }
+                if separator != " (" {
+                    write!(w, ")")?;
+                }
+
+                writeln!(w)?;
+            }
+
+            if !recipes.is_empty() {
+                writeln!(w, "\nrecipes:")?;
+                for (recipe, report) in recipes.iter().zip(&recipe_reports.0) {
+                    let RecipeReport { made_count, incomplete_count, short_of } = report;
+
+                    write!(
+                        w,
+                        "    {}: made {made_count} times, incomplete {incomplete_count} times",
+                        recipe.name,
+                    )?;
+
+                    let mut separator = " (short of ";
+                    for (food_type, count) in food_types.iter().zip(short_of) {
+                        if *count > 0 {
+                            write!(w, "{separator}{}: {count}", food_type.key)?;
+                            separator = ", ";
+                        }
+                    }
+                    if separator != " (short of " {
+                        write!(w, ")")?;
+                    }
+
+                    writeln!(w)?;
+                }
+            }
+        }
+
+        Ok(RunOutput {
+            performance,
+            terms,
+            out_count,
+            starved_count,
+        })
+    }
+
+    fn log(&mut self, entry: &LogEntry) -> Result<(), std::io::Error> {
+        if let Some(event_log) = &mut self.event_log {
+            event_log::write_entry(event_log, entry)?;
+        }
+
+        Ok(())
+    }
+
+    fn log_stock(&mut self, day: u32, event_index: u32) -> Result<(), std::io::Error> {
+        if let (Some(event_log), Some(stock)) = (&mut self.event_log, self.daily_stock.last()) {
+            for (food_type, &KeyStock { grams, items }) in self.food_types.iter().zip(stock) {
+                event_log::write_entry(
+                    &mut *event_log,
+                    &LogEntry::Stock {
+                        day,
+                        event_index,
+                        key: food_type.key.clone(),
+                        grams,
+                        items,
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn simulate_and_show(&mut self, event: Event, day: u32, event_index: u32) -> Result<(), std::io::Error> {
+        let mut tracking_steps = core::mem::take(&mut self.tracking_steps);
+        tracking_steps.clear();
+
+        simulate(
+            &mut self.rng,
+            &mut self.study,
+            &mut tracking_steps,
+            self.food_types,
+            self.substitution_policy,
+            event
+        );
+
+        for step in &tracking_steps {
+            self.out_report.record(self.food_types, step);
+            self.recipe_reports.record(self.recipes, self.food_types, step);
+
+            self.log(&log_entry(self.food_types, step, day, event_index))?;
+        }
+
+        if self.spec.show_step_by_step {
+            let w = &mut self.w;
+            let daily_totals = &mut self.daily_totals;
+
+            use TrackingStep::*;
+            for step in &tracking_steps {
+                match step {
+                    Starved(grams) => {
+                        writeln!(w, "Starved by {grams}g")?;
+                    }
+                    Ate { eaten, key, out_count, servings_count } => {
+                        // TODO? label when it's a substitute thing, so lots of servings are expected?
+                        writeln!(w, "Ate {eaten}g of {key} ({servings_count} servings)")?;
+
+                        if *out_count > 0 {
+                            writeln!(w, "    Ran out by {out_count}g")?;
+                        }
+
+                        daily_totals.ate += *eaten as u32;
+                    },
+                    Bought(grams, key, price) => {
+                        writeln!(w, "Bought {grams}g of {key} for {}", show_money(*price))?;
+                        daily_totals.bought += *grams as u32;
+                        daily_totals.spent += price;
+                    },
+                    Spoiled(grams, key) => {
+                        writeln!(w, "Threw out {grams}g of spoiled {key}")?;
+                        daily_totals.spoiled += *grams as u32;
+                    },
+                    Substituted { wanted, with } => {
+                        writeln!(w, "Substituted {with} for {wanted}")?;
+                    },
+                    MadeRecipe(recipe) => {
+                        writeln!(w, "Made {recipe}")?;
+                    },
+                    RecipeIncomplete { recipe, key, grams_short } => {
+                        writeln!(w, "Couldn't make {recipe}: {grams_short}g short of {key}")?;
+                    },
+                    MealStarted(slot) => {
+                        writeln!(w, "{slot:?}:")?;
+                    },
+                }
+            }
+        }
+
+        self.tracking_steps = tracking_steps;
+
+        Ok(())
+    }
+
+    fn step_event(&mut self, i: usize, event_entry: EventEntry) -> Result<(), std::io::Error> {
+        let event_count = self.events.len();
+
+        match event_entry {
+            EventEntry::InitialDayMarker => {
+                let day_number = self.day_number;
+
+                self.daily_stock.push(stock_by_key(&self.study, self.food_types));
+
+                self.log(&LogEntry::DayStart { day: day_number, event_index: i as u32 })?;
+                self.log_stock(day_number, i as u32)?;
+
+                if self.spec.show_step_by_step {
+                    writeln!(self.w, "======= Start of the First Day ==========")?;
+                    writeln!(self.w, "Day {day_number}")?;
+                    self.daily_totals = DailyTotals::default();
+                }
+            }
+            EventEntry::DayMarker => {
+                self.day_number += 1;
+                self.day_boundaries.push(self.all_stats.len());
+
+                // The day that is ending.
+                let day = self.day_number - 1;
+
+                self.simulate_and_show(Event::DayPassed, day, i as u32)?;
+
+                self.daily_stock.push(stock_by_key(&self.study, self.food_types));
+
+                self.log(&LogEntry::DayEnd { day, event_index: i as u32 })?;
+                self.log_stock(day, i as u32)?;
+
+                if self.spec.show_step_by_step {
+                    let w = &mut self.w;
+                    let DailyTotals { ate, bought, spoiled, spent } = self.daily_totals;
+
+                    writeln!(w, "============= End of Day ================")?;
+
+                    writeln!(w, "Ate: {ate}")?;
+                    writeln!(w, "Bought: {bought}")?;
+                    writeln!(w, "Spent: {}", show_money(spent))?;
+                    writeln!(w, "Spoiled: {spoiled}")?;
+                    writeln!(w, "Stock:")?;
+
+                    // TODO? sort display of items? Or should the shelf data structure just be ordered?
+                    for item in &self.study.shelf {
+                        writeln!(w, "    {}: {}g", item.key, item.grams)?;
+                    }
+
+                    writeln!(w, "Stock by food:")?;
+                    if let Some(stock) = self.daily_stock.last() {
+                        for (food_type, KeyStock { grams, items }) in self.food_types.iter().zip(stock) {
+                            writeln!(w, "    {}: {grams}g in {items} items", food_type.key)?;
+                        }
+                    }
+
+                    writeln!(w, "=========================================")?;
+                    if i < event_count - 1 {
+                        writeln!(w, "Day {}", self.day_number)?;
+                    }
+
+                    self.daily_totals = DailyTotals::default();
+                }
+
+                if i < event_count - 1 {
+                    self.log(&LogEntry::DayStart { day: self.day_number, event_index: i as u32 })?;
+                }
+            },
+            EventEntry::Event(event) => {
+                self.all_stats.push(stats(&self.study));
+
+                let day_number = self.day_number;
+
+                self.simulate_and_show(event, day_number, i as u32)?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod simulation_works {
+    use super::*;
+    use crate::event_source::{BuyIfHalfEmptyParams, BuyNOfEverythingParams, EventSource, FixedHungerAmountParams};
+    use vec1::vec1;
+
+    fn spec() -> Spec {
+        let food_type = |key: &str, grams| food::Type::new(key, food::Option { grams, price: 100, ..food::Option::default() });
+
+        fn es_spec(source: impl EventSource + 'static) -> EventSourceSpec {
+            EventSourceSpec {
+                source: Box::new(source),
+                recurrence: vec![],
+                label: String::new(),
+            }
+        }
+
+        Spec {
+            mode: Mode::Basic(BasicExtras {
+                mode: BasicMode::Run,
+                food_types: vec1![food_type("Bread", 600), food_type("Jam", 500)],
+                initial_event_source_specs: vec1![
+                    es_spec(BuyNOfEverythingParams { n: 2 }),
+                ],
+                repeated_event_source_specs: vec1![
+                    es_spec(FixedHungerAmountParams { grams_per_day: 300, ..FixedHungerAmountParams::default() }),
+                    es_spec(BuyIfHalfEmptyParams { max_count: 2, offset: 0 }),
+                ],
+                objective: Objective::default(),
+                recipes: Vec::new(),
+                substitution_policy: SubstitutionPolicy::default(),
+            }),
+            seed: Some([7; 16]),
+            day_count_min: 10,
+            day_count_one_past_max: 11,
+            hide_summary: true,
+            ..Spec::default()
+        }
+    }
+
+    #[test]
+    fn stepping_by_day_matches_running_all_at_once() {
+        let spec = spec();
+
+        let expected = run(&spec, std::io::sink()).unwrap();
+
+        let mut simulation = Simulation::new(&spec).unwrap();
+
+        let mut days = 0;
+        while simulation.step_day().unwrap() {
+            days += 1;
+            assert_eq!(simulation.day(), days);
+            assert_eq!(simulation.daily_stock().len(), days as usize + 1);
+        }
+        assert!(simulation.is_finished());
+        assert_eq!(days as usize, simulation.day_count());
+
+        let output = simulation.finish().unwrap();
+
+        assert_eq!(output.performance, expected.performance);
+        assert_eq!(output.out_count, expected.out_count);
+        assert_eq!(output.starved_count, expected.starved_count);
+    }
+
+    #[test]
+    fn on_daily_stock() {
+        let spec = spec();
+
+        let mut simulation = Simulation::new(&spec).unwrap();
+
+        simulation.step_day().unwrap();
+
+        let daily_stock = simulation.daily_stock();
+        assert_eq!(daily_stock.len(), 2);
+        // The initial events, which buy two of everything, happen before the first day starts.
+        assert_eq!(
+            daily_stock[0],
+            vec![KeyStock { grams: 1200, items: 2 }, KeyStock { grams: 1000, items: 2 }],
+        );
+        assert_eq!(daily_stock[1], stock_by_key(&simulation.study, simulation.food_types));
+    }
+
+    #[test]
+    fn on_spending_up_to_the_last_event() {
+        let path = std::env::temp_dir().join(format!("fist-spend-{}.ndjson", std::process::id()));
+
+        let spec = Spec {
+            event_log_path: Some(path.to_string_lossy().into_owned()),
+            ..spec()
+        };
+
+        let output = run(&spec, std::io::sink()).unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let logged_spend: Money = log.lines()
+            .map(|line| match serde_json::from_str(line).unwrap() {
+                LogEntry::Bought { price, .. } => price,
+                _ => 0,
+            })
+            .sum();
+
+        assert!(logged_spend > 0);
+        assert_eq!(output.terms.spend, logged_spend as f32 * Objective::default().spend_weight);
+    }
+}
+
+#[cfg(test)]
+mod spoilage_works {
+    use super::*;
+    use vec1::vec1;
+
+    #[test]
+    fn on_unopened_and_opened_food() {
+        let food_type = |key: &str, shelf_life, opened_shelf_life| food::Type::new(
+            key,
+            food::Option { grams: 500, shelf_life, opened_shelf_life, ..food::Option::default() },
+        );
+        let food_types: FoodTypes = vec1![
+            food_type("Milk", Some(2), None),
+            food_type("Jam", None, Some(1)),
+            food_type("Rice", None, None),
+        ];
+
+        let mut rng = xs::from_seed([1; 16]);
+        let mut study = Shelf::default();
+        let mut tracking_steps = Vec::new();
+
+        for food_type in food_types.iter() {
+            study.shelf.push(Food::of_type(food_type, food_type.options[0].clone()));
+        }
+
+        macro_rules! simulate {
+            ($event: expr) => {
+                simulate(&mut rng, &mut study, &mut tracking_steps, &food_types, SubstitutionPolicy::default(), $event)
+            }
+        }
+
+        simulate!(Event::Ate("Jam".to_string(), 100));
+        simulate!(Event::DayPassed);
+
+        fn keys(study: &Shelf) -> Vec<&str> {
+            study.shelf.iter().map(|food| food.key.as_str()).collect()
+        }
+
+        assert_eq!(keys(&study), vec!["Milk", "Rice"]);
+        assert_eq!(study.perf.wasted_grams, 400);
+
+        simulate!(Event::DayPassed);
+
+        assert_eq!(keys(&study), vec!["Rice"]);
+        assert_eq!(study.perf.wasted_grams, 900);
+        assert!(matches!(
+            tracking_steps.last(),
+            Some(TrackingStep::Spoiled(500, key)) if key == "Milk"
+        ));
+    }
+
+    #[test]
+    fn on_an_empty_pack() {
+        let food_types: FoodTypes = vec1![food::Type::new(
+            "Jam",
+            food::Option { grams: 500, opened_shelf_life: Some(1), ..food::Option::default() },
+        )];
+
+        let mut rng = xs::from_seed([1; 16]);
+        let mut study = Shelf::default();
+        let mut tracking_steps = Vec::new();
+
+        study.shelf.push(Food::of_type(&food_types[0], food_types[0].options[0].clone()));
+
+        simulate(&mut rng, &mut study, &mut tracking_steps, &food_types, SubstitutionPolicy::default(), Event::Ate("Jam".to_string(), 500));
+        tracking_steps.clear();
+        simulate(&mut rng, &mut study, &mut tracking_steps, &food_types, SubstitutionPolicy::default(), Event::DayPassed);
+
+        assert!(study.shelf.is_empty());
+        assert_eq!(study.perf.wasted_grams, 0);
+        assert!(tracking_steps.is_empty(), "{tracking_steps:?}");
+    }
+}
+
+#[cfg(test)]
+mod recipes_work {
+    use super::*;
+    use vec1::vec1;
+
+    #[test]
+    fn all_or_nothing() {
+        let food_type = |key: &str| food::Type::new(key, food::Option { grams: 500, ..food::Option::default() });
+        let food_types: FoodTypes = vec1![food_type("Bread"), food_type("Jam")];
+
+        let recipe = food::Recipe {
+            name: "Jam Sandwich".to_string(),
+            ingredients: vec1![("Bread".to_string(), 100), ("Jam".to_string(), 20)],
+        };
+
+        let mut rng = xs::from_seed([1; 16]);
+        let mut study = Shelf::default();
+        let mut tracking_steps = Vec::new();
+
+        study.shelf.push(Food::of_type(&food_types[0], food_types[0].options[0].clone()));
+
+        simulate(&mut rng, &mut study, &mut tracking_steps, &food_types, SubstitutionPolicy::default(), Event::AteRecipe(recipe.clone()));
+
+        assert!(matches!(
+            &tracking_steps[..],
+            [TrackingStep::RecipeIncomplete { key, grams_short: 20, .. }, TrackingStep::Starved(120)] if key == "Jam"
+        ));
+        assert_eq!(study.shelf[0].grams, 500);
+        assert_eq!(study.perf.out_count, 20);
+        assert_eq!(study.perf.starved_count, 1);
+
+        study.shelf.push(Food::of_type(&food_types[1], food_types[1].options[0].clone()));
+        tracking_steps.clear();
+
+        simulate(&mut rng, &mut study, &mut tracking_steps, &food_types, SubstitutionPolicy::default(), Event::AteRecipe(recipe));
+
+        assert!(matches!(
+            &tracking_steps[..],
+            [TrackingStep::MadeRecipe(_), TrackingStep::Ate { .. }, TrackingStep::Ate { .. }]
+        ));
+        assert_eq!(study.shelf.iter().map(|food| food.grams).collect::<Vec<_>>(), vec![400, 480]);
+        assert_eq!(study.perf.out_count, 20);
+        assert_eq!(study.perf.starved_count, 1);
+    }
+}
+
+#[cfg(test)]
+mod substitution_works {
+    use super::*;
+    use vec1::vec1;
+
+    fn food_types() -> FoodTypes {
+        let food_type = |key: &str, serving, categories: &[&str], substitutes: &[&str]| food::Type {
+            serving: food::NonZeroGrams::new(serving).unwrap(),
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            substitutes: substitutes.iter().map(|s| s.to_string()).collect(),
+            ..food::Type::new(key, food::Option { grams: 1000, ..food::Option::default() })
+        };
+
+        vec1![
+            food_type("Milk", 250, &["Dairy"], &["Yogurt"]),
+            food_type("Frozen Brussels Sprouts", 250, &["Vegetable"], &[]),
+            food_type("Yogurt", 100, &["Dairy"], &[]),
+        ]
+    }
+
+    /// What was eaten when wanting milk, with the given foods on the shelf.
+    fn eaten_instead_of_milk(policy: SubstitutionPolicy, on_shelf: &[usize]) -> Vec<TrackingStep> {
+        let food_types = food_types();
+
+        let mut rng = xs::from_seed([1; 16]);
+        let mut study = Shelf::default();
+        let mut tracking_steps = Vec::new();
+
+        for &i in on_shelf {
+            study.shelf.push(Food::of_type(&food_types[i], food_types[i].options[0].clone()));
+        }
+
+        simulate(&mut rng, &mut study, &mut tracking_steps, &food_types, policy, Event::Ate("Milk".to_string(), 250));
+
+        tracking_steps
+    }
+
+    fn substitute(steps: &[TrackingStep]) -> Option<&str> {
+        steps.iter().find_map(|step| match step {
+            TrackingStep::Substituted { with, .. } => Some(with.as_str()),
+            _ => None,
+        })
+    }
+
+    fn starved(steps: &[TrackingStep]) -> bool {
+        steps.iter().any(|step| matches!(step, TrackingStep::Starved(_)))
+    }
+
+    #[test]
+    fn on_each_policy() {
+        let steps = eaten_instead_of_milk(SubstitutionPolicy::ServingSize, &[1, 2]);
+        assert_eq!(substitute(&steps), Some("Frozen Brussels Sprouts"));
+
+        let steps = eaten_instead_of_milk(SubstitutionPolicy::SameCategory, &[1, 2]);
+        assert_eq!(substitute(&steps), Some("Yogurt"));
+
+        let steps = eaten_instead_of_milk(SubstitutionPolicy::SameCategory, &[1]);
+        assert_eq!(substitute(&steps), None);
+        assert!(starved(&steps));
+
+        let steps = eaten_instead_of_milk(SubstitutionPolicy::Ranked, &[1, 2]);
+        assert_eq!(substitute(&steps), Some("Yogurt"));
+
+        let steps = eaten_instead_of_milk(SubstitutionPolicy::Ranked, &[1]);
+        assert!(starved(&steps));
+
+        let steps = eaten_instead_of_milk(SubstitutionPolicy::Random, &[1]);
+        assert_eq!(substitute(&steps), Some("Frozen Brussels Sprouts"));
+    }
+}
@@ -0,0 +1,68 @@
+//! Summarizing many runs of the same spec, each with its own seed.
+
+use crate::{aggregate, basic, parallel, xs};
+use crate::types::{BatchSpec, Res, Spec};
+
+/// Runs the spec once for each of `sample_count` seeds derived from the spec's seed, then writes
+/// out a summary of each measure across the runs.
+pub fn run(
+    spec: &Spec,
+    BatchSpec {
+        sample_count,
+    }: &BatchSpec,
+    mut output: impl std::io::Write,
+) -> Res<()> {
+    let seeds = xs::chained_seeds(spec.seed.unwrap_or_default(), *sample_count as usize);
+
+    let run_outputs = parallel::map(spec.worker_count, &seeds, |&seed| {
+        basic::run(
+            &Spec {
+                seed: Some(seed),
+                ..spec.without_files()
+            },
+            std::io::sink(),
+        )
+    });
+
+    let mut performances = Vec::with_capacity(seeds.len());
+    let mut out_counts = Vec::with_capacity(seeds.len());
+    let mut starved_counts = Vec::with_capacity(seeds.len());
+
+    for run_output in run_outputs {
+        let run_output = run_output?;
+
+        performances.push(run_output.performance as f32);
+        out_counts.push(run_output.out_count as f32);
+        starved_counts.push(run_output.starved_count as f32);
+    }
+
+    writeln!(output, "sample_count: {sample_count},")?;
+
+    for (label, samples) in [
+        ("performance (closer to 0 is better)", &performances),
+        ("out_count (closer to 0 is better)", &out_counts),
+        ("starved_count (closer to 0 is better)", &starved_counts),
+    ] {
+        let aggregate::Summary {
+            mean,
+            median,
+            std_dev,
+            min,
+            max,
+            percentiles,
+        } = aggregate::summarize(samples);
+
+        writeln!(output, "{label}: (")?;
+        writeln!(output, "    mean: {mean},")?;
+        writeln!(output, "    median: {median},")?;
+        writeln!(output, "    std_dev: {std_dev},")?;
+        writeln!(output, "    min: {min},")?;
+        writeln!(output, "    max: {max},")?;
+        for (percent, value) in aggregate::SUMMARY_PERCENTILES.iter().zip(percentiles) {
+            writeln!(output, "    p{percent}: {value},")?;
+        }
+        writeln!(output, "),")?;
+    }
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
-use crate::types::{self, food, BasicMode, BasicExtras, DayCount, FixedServingsAmountParams, FoodTypes, Mode, PrintCallsSpec, RawEventSourceSpecKind, Recurrence, Res, RollOnePastMax, Seed, SearchSpec, ShoppingCount, Spec, Target};
+use crate::event_log::LogEntry;
+use crate::event_source::Registry;
+use crate::parallel::{self, WorkerCount};
+use crate::types::{self, food, Aggregation, BasicMode, BasicExtras, BatchSpec, DayCount, FoodTypes, Mode, Objective, Optimizer, OutputFormat, PrintCallsSpec, Recurrence, ReplaySpec, Res, SampleCount, Seed, SearchSpec, SearchTarget, Spec, SubstitutionPolicy, Sweep, Target};
 use std::collections::HashSet;
+use vec1::Vec1;
 
 xflags::xflags! {
     cmd args {
@@ -24,6 +28,83 @@ impl core::fmt::Debug for DuplicateKeyError {
 
 impl std::error::Error for DuplicateKeyError {}
 
+struct UnknownSubstituteError {
+    key: types::food::Key,
+    substitute: types::food::Key,
+}
+
+impl core::fmt::Display for UnknownSubstituteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Unknown substitute \"{}\" for food type: {}", self.substitute, self.key)
+    }
+}
+
+impl core::fmt::Debug for UnknownSubstituteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for UnknownSubstituteError {}
+
+struct InvalidWeightError {
+    key: types::food::Key,
+    weight: f32,
+}
+
+impl core::fmt::Display for InvalidWeightError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Weight must be a non-negative number, not {} for food type: {}", self.weight, self.key)
+    }
+}
+
+impl core::fmt::Debug for InvalidWeightError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for InvalidWeightError {}
+
+struct RequiredKeyError {
+    mode: RawBasicMode,
+    key_name: String,
+}
+
+impl core::fmt::Display for RequiredKeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Key \"{}\" is required for basic_mode: {:?}", self.key_name, self.mode)
+    }
+}
+
+impl core::fmt::Debug for RequiredKeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for RequiredKeyError {}
+
+struct ReplayLogError {
+    path: String,
+    line_number: usize,
+    message: String,
+}
+
+impl core::fmt::Display for ReplayLogError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line_number, self.message)
+    }
+}
+
+impl core::fmt::Debug for ReplayLogError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for ReplayLogError {}
+
 struct ExcessDataError {
     mode: RawMode,
     key_name: String,
@@ -43,6 +124,63 @@ impl core::fmt::Debug for ExcessDataError {
 
 impl std::error::Error for ExcessDataError {}
 
+struct MovedKeyError {
+    key_name: &'static str,
+    new_key_name: &'static str,
+}
+
+impl core::fmt::Display for MovedKeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Key \"{}\" has moved to \"{}\", so it is ignored", self.key_name, self.new_key_name)
+    }
+}
+
+impl core::fmt::Debug for MovedKeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for MovedKeyError {}
+
+struct InvalidEventSourceError {
+    key_name: String,
+    message: String,
+}
+
+impl core::fmt::Display for InvalidEventSourceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid event source spec \"{}\": {}", self.key_name, self.message)
+    }
+}
+
+impl core::fmt::Debug for InvalidEventSourceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for InvalidEventSourceError {}
+
+struct InvalidValueError {
+    key_name: String,
+    message: String,
+}
+
+impl core::fmt::Display for InvalidValueError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid value for \"{}\": {}", self.key_name, self.message)
+    }
+}
+
+impl core::fmt::Debug for InvalidValueError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for InvalidValueError {}
+
 struct AtLeastOneRequiredError {
     mode: RawMode,
     key_name: String,
@@ -62,6 +200,56 @@ impl core::fmt::Debug for AtLeastOneRequiredError {
 
 impl std::error::Error for AtLeastOneRequiredError {}
 
+struct InvalidTargetError(String);
+
+impl core::fmt::Display for InvalidTargetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid target: {}", self.0)
+    }
+}
+
+impl core::fmt::Debug for InvalidTargetError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for InvalidTargetError {}
+
+struct InvalidRecipeError(String);
+
+impl core::fmt::Display for InvalidRecipeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid recipe: {}", self.0)
+    }
+}
+
+impl core::fmt::Debug for InvalidRecipeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for InvalidRecipeError {}
+
+struct TooManyTargetsError {
+    count: usize,
+}
+
+impl core::fmt::Display for TooManyTargetsError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} targets given, but at most {} are supported", self.count, types::MAX_SEARCH_TARGETS)
+    }
+}
+
+impl core::fmt::Debug for TooManyTargetsError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for TooManyTargetsError {}
+
 #[derive(Debug, serde::Deserialize)]
 enum RawMode {
     Minimal,
@@ -80,38 +268,15 @@ impl core::fmt::Display for RawMode {
 
 #[derive(Debug, serde::Deserialize)]
 struct RawEventSourceSpec {
-    pub kind: RawEventSourceSpecKind,
+    /// The name of the source in the `Registry`.
+    pub kind: String,
     #[serde(default)]
     pub recurrence: Recurrence,
-    // All of the fields from all of the params
-    #[serde(default)]
-    pub grams_per_day: food::Grams,
-    #[serde(default)]
-    pub servings_per_day: food::Servings,
-    #[serde(default)]
-    pub minimum_purchase_servings: food::Servings,
-    #[serde(default)]
-    pub buy_count: u8,
-    #[serde(default)]
-    pub count: u16,
-    #[serde(default)]
-    pub max_count: u16,
-    #[serde(default)]
-    pub offset: usize,
     #[serde(default)]
-    pub roll_one_past_max: RollOnePastMax,
-    #[serde(default)]
-    pub fullness_threshold: types::FullnessThreshold,
-    #[serde(default)]
-    pub key_to_eat: food::Key,
-    #[serde(default)]
-    pub grams_to_eat: food::Grams,
-    #[serde(default)]
-    pub key_to_buy: food::Key,
-    #[serde(default)]
-    pub grams_to_buy: food::Grams,
-    #[serde(default)]
-    pub n: ShoppingCount,
+    pub label: String,
+    /// The params of the source, which depend on the kind.
+    #[serde(flatten)]
+    pub params: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Clone, Debug, Default, serde::Deserialize)]
@@ -120,6 +285,59 @@ pub enum RawBasicMode {
     Run,
     Search,
     PrintCalls,
+    Batch,
+    Replay,
+}
+
+/// Allows a key to be set to either a single value, or a list of them.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<A> {
+    One(A),
+    Many(Vec<A>),
+}
+
+impl <A: Default> Default for OneOrMany<A> {
+    fn default() -> Self {
+        Self::One(A::default())
+    }
+}
+
+impl <A> OneOrMany<A> {
+    fn into_vec(self) -> Vec<A> {
+        match self {
+            Self::One(a) => vec![a],
+            Self::Many(v) => v,
+        }
+    }
+}
+
+/// Either just a target, or a target along with settings for searching over it.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum RawSearchTarget {
+    Path(Target),
+    Full {
+        target: Target,
+        #[serde(default)]
+        lower: Option<f32>,
+        #[serde(default)]
+        upper: Option<f32>,
+        /// Defaults to whether the targeted field holds whole numbers.
+        #[serde(default)]
+        integer: Option<bool>,
+        /// Default to `basic_offset` and `basic_length`.
+        #[serde(default)]
+        offset: Option<f32>,
+        #[serde(default)]
+        length: Option<f32>,
+    },
+}
+
+impl Default for RawSearchTarget {
+    fn default() -> Self {
+        Self::Path(Target::default())
+    }
 }
 
 fn default_length() -> f32 {
@@ -130,30 +348,116 @@ fn default_step() -> f32 {
     1. / 64.
 }
 
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub enum RawAggregation {
+    #[default]
+    Mean,
+    WorstCase,
+    Percentile,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub enum RawOptimizer {
+    #[default]
+    NelderMead,
+    Grid,
+    RandomRestart,
+    Evolution,
+}
+
+fn default_grid_steps() -> u16 {
+    16
+}
+
+fn default_restarts() -> u16 {
+    8
+}
+
+fn default_population() -> u16 {
+    16
+}
+
+fn default_percentile() -> f32 {
+    90.
+}
+
+fn default_max_iterations() -> u16 {
+    100
+}
+
 #[derive(serde::Deserialize)]
 struct RawSpec {
     // All modes
     pub mode: RawMode,
     pub seed: Option<Seed>,
+    /// Defaults to the available parallelism.
+    #[serde(default)]
+    pub worker_count: Option<WorkerCount>,
     pub day_count_min: DayCount,
     pub day_count_one_past_max: DayCount,
     // Basic extras
     #[serde(default)]
     pub food_types: Vec<food::Type>,
     #[serde(default)]
+    pub recipes: Vec<food::Recipe>,
+    #[serde(default)]
     pub initial_event_source_specs: Vec<RawEventSourceSpec>,
     #[serde(default)]
     pub repeated_event_source_specs: Vec<RawEventSourceSpec>,
     #[serde(default)]
     pub basic_mode: RawBasicMode,
     #[serde(default)]
-    pub basic_target: Target,
+    pub basic_target: OneOrMany<RawSearchTarget>,
     #[serde(default = "default_length")]
     pub basic_length: f32,
     #[serde(default)]
     pub basic_offset: f32,
     #[serde(default = "default_step")]
     pub basic_step: f32,
+    /// The `y` versions of the above are for the second target of a PrintCalls grid.
+    /// They default to the same as the `x` versions.
+    #[serde(default)]
+    pub basic_y_length: Option<f32>,
+    #[serde(default)]
+    pub basic_y_offset: Option<f32>,
+    #[serde(default)]
+    pub basic_y_step: Option<f32>,
+    /// If not given, PrintCalls writes the `(x, performance)` tuples it always has.
+    #[serde(default)]
+    pub basic_format: Option<OutputFormat>,
+    #[serde(default)]
+    pub basic_chart_path: Option<String>,
+    #[serde(default)]
+    pub basic_sample_count: Option<SampleCount>,
+    #[serde(default)]
+    pub basic_aggregation: RawAggregation,
+    #[serde(default = "default_percentile")]
+    pub basic_percentile: f32,
+    #[serde(default)]
+    pub basic_optimizer: RawOptimizer,
+    #[serde(default = "default_grid_steps")]
+    pub basic_grid_steps: u16,
+    #[serde(default = "default_restarts")]
+    pub basic_restarts: u16,
+    #[serde(default = "default_population")]
+    pub basic_population: u16,
+    #[serde(default = "default_max_iterations")]
+    pub basic_max_iterations: u16,
+    #[serde(default)]
+    pub basic_x_tolerance: f32,
+    #[serde(default)]
+    pub basic_y_tolerance: f32,
+    #[serde(default)]
+    pub basic_show_trace: bool,
+    #[serde(default)]
+    pub basic_replay_path: Option<String>,
+    #[serde(default)]
+    pub objective: Objective,
+    /// Replaced by `objective.spend_weight`. Only read so that old configs get a warning.
+    #[serde(default)]
+    pub spend_weight: Option<f32>,
+    #[serde(default)]
+    pub substitution_policy: SubstitutionPolicy,
     // Output Flags section
     // Designed such that all false is a good default.
     #[serde(default)]
@@ -164,9 +468,22 @@ struct RawSpec {
     pub show_items: bool,
     #[serde(default)]
     pub show_step_by_step: bool,
+    #[serde(default)]
+    pub stock_chart_path: Option<String>,
+    #[serde(default)]
+    pub stock_series_path: Option<String>,
+    #[serde(default)]
+    pub stock_series_format: OutputFormat,
+    #[serde(default)]
+    pub event_log_path: Option<String>,
 }
 
 pub fn get_spec() -> Res<Spec> {
+    get_spec_with(&Registry::default())
+}
+
+/// Like `get_spec`, but the event source specs can use any of the kinds in the given registry.
+pub fn get_spec_with(registry: &Registry) -> Res<Spec> {
     let args = Args::from_env()?;
 
     let mut builder = config::Config::builder()
@@ -193,11 +510,23 @@ pub fn get_spec() -> Res<Spec> {
             $( spec.$field = unvalidated_spec.$field; )+
         }
     }
-    assign!(seed day_count_min day_count_one_past_max hide_summary show_grams show_items show_step_by_step);
+    assign!(seed day_count_min day_count_one_past_max hide_summary show_grams show_items show_step_by_step stock_chart_path stock_series_path stock_series_format event_log_path);
+
+    spec.worker_count = unvalidated_spec.worker_count.unwrap_or_else(parallel::default_worker_count);
+
+    if unvalidated_spec.spend_weight.is_some() {
+        eprintln!(
+            "Warning: {}",
+            MovedKeyError {
+                key_name: "spend_weight",
+                new_key_name: "objective.spend_weight",
+            },
+        );
+    }
 
     spec.mode = match &unvalidated_spec.mode {
         RawMode::Minimal => {
-            if unvalidated_spec.food_types.len() > 0 {
+            if !unvalidated_spec.food_types.is_empty() {
                 // TODO? A strict run mode that makes this a hard error?
                 eprintln!(
                     "Warning: {}",
@@ -208,10 +537,28 @@ pub fn get_spec() -> Res<Spec> {
                 );
             }
 
+            if !unvalidated_spec.recipes.is_empty() {
+                // TODO? A strict run mode that makes this a hard error?
+                eprintln!(
+                    "Warning: {}",
+                    ExcessDataError{
+                        mode: RawMode::Minimal,
+                        key_name: "recipes".to_string(),
+                    },
+                );
+            }
+
             Mode::Minimal
         },
         RawMode::Basic => {
-            let food_types: FoodTypes = unvalidated_spec.food_types.try_into()?;
+            let mut food_types: FoodTypes = unvalidated_spec.food_types.try_into()?;
+
+            for food_type in food_types.iter_mut() {
+                for option in food_type.options.iter_mut() {
+                    option.shelf_life = option.shelf_life.or(food_type.shelf_life);
+                    option.opened_shelf_life = option.opened_shelf_life.or(food_type.opened_shelf_life);
+                }
+            }
 
             let mut seen = HashSet::with_capacity(food_types.len());
 
@@ -223,225 +570,194 @@ pub fn get_spec() -> Res<Spec> {
                 seen.insert(food_type.key.clone());
             }
 
-            fn is_default<T: PartialEq + Default>(thing: &T) -> bool {
-                PartialEq::eq(thing, &T::default())
+            for food_type in food_types.iter() {
+                if !(food_type.weight.is_finite() && food_type.weight >= 0.) {
+                    return Err(Box::from(InvalidWeightError {
+                        key: food_type.key.clone(),
+                        weight: food_type.weight,
+                    }));
+                }
+
+                for substitute in &food_type.substitutes {
+                    if !seen.contains(substitute) {
+                        return Err(Box::from(UnknownSubstituteError {
+                            key: food_type.key.clone(),
+                            substitute: substitute.clone(),
+                        }));
+                    }
+                }
             }
 
-            macro_rules! excess_data_check {
-                ($specs: ident [$i: ident] $error_key: literal : $($key: ident)+) => ({
-                    // TODO reverse the meaning of the keys, so we don't need to
-                    // update every old one when adding a new key.
+            let recipes = unvalidated_spec.recipes;
+
+            let mut seen_names = HashSet::with_capacity(recipes.len());
+
+            for recipe in &recipes {
+                if !seen_names.insert(&recipe.name) {
+                    return Err(Box::from(InvalidRecipeError(format!("Duplicate name \"{}\"", recipe.name))));
+                }
+
+                let mut seen_keys = HashSet::with_capacity(recipe.ingredients.len());
+
+                for (key, _) in recipe.ingredients.iter() {
+                    if !seen.contains(key) {
+                        return Err(Box::from(InvalidRecipeError(format!("\"{}\" has an unknown ingredient \"{key}\"", recipe.name))));
+                    }
 
-                    $(
-                        if !is_default(&$specs[$i].$key) {
+                    if !seen_keys.insert(key) {
+                        return Err(Box::from(InvalidRecipeError(format!("\"{}\" has \"{key}\" more than once", recipe.name))));
+                    }
+                }
+            }
+
+            let validate_event_source_specs = |
+                error_key: &str,
+                raw_specs: &[RawEventSourceSpec]
+            | -> Res<Vec1<types::EventSourceSpec>> {
+                let mut specs = Vec::with_capacity(raw_specs.len());
+
+                for (i, raw_spec) in raw_specs.iter().enumerate() {
+                    let key_name = format!("{error_key}[{i}]");
+
+                    let source = registry.build(&raw_spec.kind, serde_json::Value::Object(raw_spec.params.clone()))
+                        .ok_or_else(|| InvalidEventSourceError {
+                            key_name: key_name.clone(),
+                            message: format!(
+                                "Unknown kind \"{}\". Known kinds: {:?}",
+                                raw_spec.kind,
+                                registry.names().collect::<Vec<_>>(),
+                            ),
+                        })?
+                        .map_err(|error| InvalidEventSourceError {
+                            key_name: key_name.clone(),
+                            message: error.to_string(),
+                        })?;
+
+                    source.validate(&food_types, &recipes)
+                        .map_err(|message| InvalidEventSourceError {
+                            key_name: key_name.clone(),
+                            message,
+                        })?;
+
+                    let field_names = source.params().field_names();
+                    for param_key in raw_spec.params.keys() {
+                        if !field_names.contains(&param_key.as_str()) {
                             // TODO? A strict run mode that makes this a hard error?
                             eprintln!(
                                 "Warning: {}",
                                 ExcessDataError{
                                     mode: RawMode::Basic,
-                                    key_name: format!("{}[{}].{} for {:?}", $error_key, $i, stringify!($key), $specs[$i].kind),
+                                    key_name: format!("{key_name}.{param_key} for {}", raw_spec.kind),
                                 },
                             );
                         }
-                    )+
-                })
-            }
+                    }
 
-            macro_rules! validate_event_source_specs {
-                ($error_key: literal : $specs: expr) => ({
-                    let specs = &$specs;
-                    let mut specs_vec = Vec::with_capacity(specs.len());
-
-                    for i in 0..specs.len() {
-                        use crate::types::{
-                            EventSourceSpec as ESS,
-                            EventSourceSpecKind as ESSK,
-                            RawEventSourceSpecKind::*
-                        };
-
-                        let e_s_spec = &specs[i];
-
-                        use crate::types::{
-                            BuyExactlyParams,
-                            EatExactlyParams,
-                            FixedHungerAmountParams,
-                            RandomEventParams,
-                            ShopSomeDaysParams,
-                            BuyRandomVarietyParams,
-                            BuyIfHalfEmptyParams,
-                            BuyAllBasedOnFullnessParams,
-                            BuyNOfEverythingParams,
-                        };
-
-                        match e_s_spec.kind {
-                            BuyIfBelowThreshold => {
-                                excess_data_check!(
-                                    specs[i] $error_key : grams_per_day buy_count roll_one_past_max count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::BuyIfBelowThreshold(BuyAllBasedOnFullnessParams {
-                                            max_count: e_s_spec.max_count,
-                                            offset: e_s_spec.offset,
-                                            fullness_threshold: e_s_spec.fullness_threshold,
-                                            minimum_purchase_servings: e_s_spec.minimum_purchase_servings,
-                                        }),
-                                    },
-                                );
-                            },
-                            BuyIfHalfEmpty => {
-                                excess_data_check!(
-                                    specs[i] $error_key : grams_per_day buy_count roll_one_past_max count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::BuyIfHalfEmpty(BuyIfHalfEmptyParams {
-                                            max_count: e_s_spec.max_count,
-                                            offset: e_s_spec.offset,
-                                        }),
-                                    },
-                                );
-                            },
-                            BuyRandomVariety => {
-                                excess_data_check!(
-                                    specs[i] $error_key : grams_per_day buy_count roll_one_past_max max_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::BuyRandomVariety(BuyRandomVarietyParams {
-                                            count: e_s_spec.count,
-                                            offset: e_s_spec.offset,
-                                        }),
-                                    },
-                                );
-                            }
-                            BuyNOfEverything => {
-                                excess_data_check!(
-                                    specs[i] $error_key : grams_per_day buy_count roll_one_past_max max_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::BuyNOfEverything(BuyNOfEverythingParams {
-                                            n: e_s_spec.n,
-                                        }),
-                                    },
-                                );
-                            }
-                            BuyExactly => {
-                                excess_data_check!(
-                                    specs[i] $error_key : buy_count roll_one_past_max max_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::BuyExactly(BuyExactlyParams {
-                                            key_to_buy: e_s_spec.key_to_buy.clone(),
-                                            grams_to_buy: e_s_spec.grams_to_buy,
-                                        }),
-                                    },
-                                );
-                            },
-                            EatExactly => {
-                                excess_data_check!(
-                                    specs[i] $error_key : buy_count roll_one_past_max max_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::EatExactly(EatExactlyParams {
-                                            key_to_eat: e_s_spec.key_to_eat.clone(),
-                                            grams_to_eat: e_s_spec.grams_to_eat,
-                                        }),
-                                    },
-                                );
-                            },
-                            FixedHungerAmount => {
-                                excess_data_check!(
-                                    specs[i] $error_key : buy_count roll_one_past_max max_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::FixedHungerAmount(FixedHungerAmountParams {
-                                            grams_per_day: e_s_spec.grams_per_day
-                                        }),
-                                    },
-                                );
-                            },
-                            FixedServingsAmount => {
-                                excess_data_check!(
-                                    specs[i] $error_key : buy_count roll_one_past_max max_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::FixedServingsAmount(FixedServingsAmountParams {
-                                            servings_per_day: e_s_spec.servings_per_day
-                                        }),
-                                    },
-                                );
-                            },
-                            ShopSomeDays => {
-                                excess_data_check!(
-                                    specs[i] $error_key : grams_per_day
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::ShopSomeDays(ShopSomeDaysParams {
-                                            buy_count: e_s_spec.buy_count,
-                                            roll_one_past_max: e_s_spec.roll_one_past_max,
-                                        }),
-                                    },
-                                );
-                            },
-                            RandomEvent => {
-                                excess_data_check!(
-                                    specs[i] $error_key : grams_per_day buy_count
-                                );
-
-                                specs_vec.push(
-                                    ESS {
-                                        recurrence: e_s_spec.recurrence.clone(),
-                                        kind: ESSK::RandomEvent(RandomEventParams {
-                                            roll_one_past_max: e_s_spec.roll_one_past_max,
-                                        }),
-                                    },
-                                );
-                            },
+                    specs.push(types::EventSourceSpec {
+                        source,
+                        recurrence: raw_spec.recurrence.clone(),
+                        label: raw_spec.label.clone(),
+                    });
+                }
+
+                specs
+                    .try_into()
+                    .map_err(
+                        |_| Box::from(AtLeastOneRequiredError {
+                            mode: RawMode::Basic,
+                            key_name: error_key.to_string(),
+                        })
+                    )
+            };
+
+            let initial_event_source_specs: Vec1<types::EventSourceSpec> = validate_event_source_specs(
+                "initial_event_source_specs", &unvalidated_spec.initial_event_source_specs
+            )?;
+            let repeated_event_source_specs: Vec1<types::EventSourceSpec> = validate_event_source_specs(
+                "repeated_event_source_specs", &unvalidated_spec.repeated_event_source_specs
+            )?;
+
+            // Only checked in the modes that use the targets.
+            let validate_targets = || -> Res<Vec1<SearchTarget>> {
+                let mut targets = Vec::new();
+
+                for raw_target in unvalidated_spec.basic_target.clone().into_vec() {
+                    let (target, lower, upper, integer, offset, length) = match raw_target {
+                        RawSearchTarget::Path(target) => (target, None, None, None, None, None),
+                        RawSearchTarget::Full { target, lower, upper, integer, offset, length } =>
+                            (target, lower, upper, integer, offset, length),
+                    };
+
+                    target.validate(&initial_event_source_specs, &repeated_event_source_specs)
+                        .map_err(InvalidTargetError)?;
+
+                    for bound in [lower, upper].into_iter().flatten() {
+                        if !bound.is_finite() {
+                            return Err(Box::from(InvalidTargetError(
+                                format!("Bound {bound} of target \"{target}\" is not a finite number")
+                            )));
                         }
                     }
 
-                    specs_vec
-                        .try_into()
-                        .map_err(
-                            |_| AtLeastOneRequiredError {
-                                mode: RawMode::Basic,
-                                key_name: $error_key.to_string(),
-                            }
-                        )?
-                })
-            }
+                    if let (Some(lower), Some(upper)) = (lower, upper) && lower > upper {
+                        return Err(Box::from(InvalidTargetError(
+                            format!("Lower bound {lower} of target \"{target}\" is above its upper bound {upper}")
+                        )));
+                    }
 
-            let initial_event_source_specs = validate_event_source_specs!(
-                "initial_event_source_specs" : unvalidated_spec.initial_event_source_specs
-            );
-            let repeated_event_source_specs = validate_event_source_specs!(
-                "repeated_event_source_specs" : unvalidated_spec.repeated_event_source_specs
-            );
+                    targets.push(SearchTarget {
+                        integer: integer.unwrap_or_else(
+                            || target.is_integer(&initial_event_source_specs, &repeated_event_source_specs)
+                        ),
+                        target,
+                        lower,
+                        upper,
+                        offset: offset.unwrap_or(unvalidated_spec.basic_offset),
+                        length: length.unwrap_or(unvalidated_spec.basic_length),
+                    });
+                }
+
+                let targets: Vec1<SearchTarget> = targets.try_into()
+                    .map_err(
+                        |_| AtLeastOneRequiredError {
+                            mode: RawMode::Basic,
+                            key_name: "basic_target".to_string(),
+                        }
+                    )?;
+
+                Ok(targets)
+            };
+
+            // Only checked in the modes that use the sample count.
+            let validate_sample_count = |default: SampleCount| -> Res<SampleCount> {
+                match unvalidated_spec.basic_sample_count.unwrap_or(default) {
+                    0 => Err(Box::from(InvalidValueError {
+                        key_name: "basic_sample_count".to_string(),
+                        message: "At least one sample is required".to_string(),
+                    })),
+                    sample_count => Ok(sample_count),
+                }
+            };
+
+            let validate_aggregation = || -> Res<Aggregation> {
+                Ok(match unvalidated_spec.basic_aggregation {
+                    RawAggregation::Mean => Aggregation::Mean,
+                    RawAggregation::WorstCase => Aggregation::WorstCase,
+                    RawAggregation::Percentile => {
+                        let percentile = unvalidated_spec.basic_percentile;
+
+                        // Also rejects NaN.
+                        if !(0. ..=100.).contains(&percentile) {
+                            return Err(Box::from(InvalidValueError {
+                                key_name: "basic_percentile".to_string(),
+                                message: format!("Must be from 0 to 100, not {percentile}"),
+                            }));
+                        }
+
+                        Aggregation::Percentile(percentile)
+                    },
+                })
+            };
 
             match &unvalidated_spec.basic_mode {
                 RawBasicMode::Run => {
@@ -450,31 +766,221 @@ pub fn get_spec() -> Res<Spec> {
                         food_types,
                         initial_event_source_specs,
                         repeated_event_source_specs,
+                        objective: unvalidated_spec.objective,
+                        substitution_policy: unvalidated_spec.substitution_policy,
+                        recipes,
                     })
                 },
                 RawBasicMode::Search => {
+                    let targets = validate_targets()?;
+
+                    let optimizer = match unvalidated_spec.basic_optimizer {
+                        RawOptimizer::NelderMead => Optimizer::NelderMead,
+                        RawOptimizer::Grid => {
+                            let steps = unvalidated_spec.basic_grid_steps;
+
+                            if steps == 0 {
+                                return Err(Box::from(InvalidValueError {
+                                    key_name: "basic_grid_steps".to_string(),
+                                    message: "At least one step is required".to_string(),
+                                }));
+                            }
+
+                            let point_count = (steps as usize).checked_pow(targets.len() as u32);
+                            if point_count.is_none_or(|count| count > Sweep::MAX_POINTS) {
+                                return Err(Box::from(InvalidValueError {
+                                    key_name: "basic_grid_steps".to_string(),
+                                    message: format!(
+                                        "{steps} steps for each of {} targets is more than the most allowed calls, {}",
+                                        targets.len(),
+                                        Sweep::MAX_POINTS,
+                                    ),
+                                }));
+                            }
+
+                            Optimizer::Grid { steps }
+                        },
+                        RawOptimizer::RandomRestart => Optimizer::RandomRestart { restarts: unvalidated_spec.basic_restarts },
+                        RawOptimizer::Evolution => Optimizer::Evolution { population: unvalidated_spec.basic_population },
+                    };
+
                     Mode::Basic(BasicExtras {
                         mode: BasicMode::Search(SearchSpec {
-                            target: unvalidated_spec.basic_target,
-                            length: unvalidated_spec.basic_length,
-                            offset: unvalidated_spec.basic_offset,
+                            targets: if targets.len() > types::MAX_SEARCH_TARGETS {
+                                return Err(Box::from(TooManyTargetsError { count: targets.len() }));
+                            } else {
+                                targets
+                            },
+                            sample_count: validate_sample_count(1)?,
+                            aggregation: validate_aggregation()?,
+                            optimizer,
+                            max_iterations: unvalidated_spec.basic_max_iterations,
+                            x_tolerance: unvalidated_spec.basic_x_tolerance,
+                            y_tolerance: unvalidated_spec.basic_y_tolerance,
+                            show_trace: unvalidated_spec.basic_show_trace,
                         }),
                         food_types,
                         initial_event_source_specs,
                         repeated_event_source_specs,
+                        objective: unvalidated_spec.objective,
+                        substitution_policy: unvalidated_spec.substitution_policy,
+                        recipes,
                     })
                 },
                 RawBasicMode::PrintCalls => {
-                    Mode::Basic(BasicExtras {
-                        mode: BasicMode::PrintCalls(PrintCallsSpec {
-                            target: unvalidated_spec.basic_target,
+                    let targets = validate_targets()?;
+
+                    let validate_sweep = |sweep: Sweep, step_key: &str| -> Res<Sweep> {
+                        if !(sweep.step.is_finite() && sweep.step > 0.) {
+                            return Err(Box::from(InvalidValueError {
+                                key_name: step_key.to_string(),
+                                message: format!("The step must be a finite number above 0, not {}", sweep.step),
+                            }));
+                        }
+
+                        if sweep.point_count() > Sweep::MAX_POINTS {
+                            return Err(Box::from(InvalidValueError {
+                                key_name: step_key.to_string(),
+                                message: format!(
+                                    "Stepping from {} for {} by {} gives more than the most allowed calls, {}",
+                                    sweep.offset,
+                                    sweep.length,
+                                    sweep.step,
+                                    Sweep::MAX_POINTS,
+                                ),
+                            }));
+                        }
+
+                        Ok(sweep)
+                    };
+
+                    let x = validate_sweep(
+                        Sweep {
+                            target: targets.first().target.clone(),
                             length: unvalidated_spec.basic_length,
                             offset: unvalidated_spec.basic_offset,
                             step: unvalidated_spec.basic_step,
+                        },
+                        "basic_step",
+                    )?;
+
+                    if targets.len() > 2 {
+                        // TODO? A strict run mode that makes this a hard error?
+                        eprintln!(
+                            "Warning: {}",
+                            ExcessDataError{
+                                mode: RawMode::Basic,
+                                key_name: "basic_target[2..] for PrintCalls".to_string(),
+                            },
+                        );
+                    }
+
+                    let y = match targets.get(1) {
+                        Some(search_target) => {
+                            let y = validate_sweep(
+                                Sweep {
+                                    target: search_target.target.clone(),
+                                    length: unvalidated_spec.basic_y_length.unwrap_or(unvalidated_spec.basic_length),
+                                    offset: unvalidated_spec.basic_y_offset.unwrap_or(unvalidated_spec.basic_offset),
+                                    step: unvalidated_spec.basic_y_step.unwrap_or(unvalidated_spec.basic_step),
+                                },
+                                "basic_y_step",
+                            )?;
+
+                            if x.point_count().saturating_mul(y.point_count()) > Sweep::MAX_POINTS {
+                                return Err(Box::from(InvalidValueError {
+                                    key_name: "basic_y_step".to_string(),
+                                    message: format!(
+                                        "A grid of {} by {} points is more than the most allowed calls, {}",
+                                        x.point_count(),
+                                        y.point_count(),
+                                        Sweep::MAX_POINTS,
+                                    ),
+                                }));
+                            }
+
+                            Some(y)
+                        },
+                        None => None,
+                    };
+
+                    Mode::Basic(BasicExtras {
+                        mode: BasicMode::PrintCalls(PrintCallsSpec {
+                            x,
+                            y,
+                            format: unvalidated_spec.basic_format,
+                            chart_path: unvalidated_spec.basic_chart_path.clone(),
+                        }),
+                        food_types,
+                        initial_event_source_specs,
+                        repeated_event_source_specs,
+                        objective: unvalidated_spec.objective,
+                        substitution_policy: unvalidated_spec.substitution_policy,
+                        recipes,
+                    })
+                },
+                RawBasicMode::Replay => {
+                    let Some(path) = &unvalidated_spec.basic_replay_path else {
+                        return Err(Box::from(RequiredKeyError {
+                            mode: RawBasicMode::Replay,
+                            key_name: "basic_replay_path".to_string(),
+                        }));
+                    };
+
+                    let log_error = |line_number: usize, message: String| ReplayLogError {
+                        path: path.clone(),
+                        line_number,
+                        message,
+                    };
+
+                    let mut entries = Vec::new();
+
+                    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+                        let line_number = i + 1;
+
+                        if line.trim().is_empty() {
+                            continue
+                        }
+
+                        let entry: LogEntry = serde_json::from_str(line)
+                            .map_err(|e| log_error(line_number, e.to_string()))?;
+
+                        match &entry {
+                            LogEntry::Ate { key, .. } | LogEntry::Bought { key, .. }
+                            if !food_types.iter().any(|food_type| &food_type.key == key) => {
+                                return Err(Box::from(log_error(line_number, format!("Unknown food type key: {key}"))));
+                            },
+                            LogEntry::MadeRecipe { recipe, .. } | LogEntry::RecipeIncomplete { recipe, .. }
+                            if !recipes.iter().any(|r| &r.name == recipe) => {
+                                return Err(Box::from(log_error(line_number, format!("Unknown recipe name: {recipe}"))));
+                            },
+                            _ => {},
+                        }
+
+                        entries.push(entry);
+                    }
+
+                    Mode::Basic(BasicExtras {
+                        mode: BasicMode::Replay(ReplaySpec { entries }),
+                        food_types,
+                        initial_event_source_specs,
+                        repeated_event_source_specs,
+                        objective: unvalidated_spec.objective,
+                        substitution_policy: unvalidated_spec.substitution_policy,
+                        recipes,
+                    })
+                },
+                RawBasicMode::Batch => {
+                    Mode::Basic(BasicExtras {
+                        mode: BasicMode::Batch(BatchSpec {
+                            sample_count: validate_sample_count(32)?,
                         }),
                         food_types,
                         initial_event_source_specs,
                         repeated_event_source_specs,
+                        objective: unvalidated_spec.objective,
+                        substitution_policy: unvalidated_spec.substitution_policy,
+                        recipes,
                     })
                 },
             }
@@ -0,0 +1,81 @@
+//! A log of everything that happened during a run, one JSON object per line (NDJSON).
+
+use std::io::Write;
+
+use crate::event_source::MealSlot;
+
+/// One line of the event log. `event_index` is the position of the event that caused this entry,
+/// counting day boundaries, so entries caused by the same event share an index.
+/// `servings` is `None` for food types that are not in the config.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "step")]
+pub enum LogEntry {
+    DayStart { day: u32, event_index: u32 },
+    DayEnd { day: u32, event_index: u32 },
+    /// How much of one food type is on the shelf. Written for each food type after the first
+    /// `DayStart`, and after each `DayEnd`.
+    Stock { day: u32, event_index: u32, key: String, grams: u32, items: u32 },
+    Ate { day: u32, event_index: u32, key: String, grams: u32, servings: Option<f32>, out_grams: u32 },
+    Bought { day: u32, event_index: u32, key: String, grams: u32, servings: Option<f32>, price: u32 },
+    Starved { day: u32, event_index: u32, grams: u32 },
+    Spoiled { day: u32, event_index: u32, key: String, grams: u32, servings: Option<f32> },
+    Substituted { day: u32, event_index: u32, key: String, substitute: String },
+    /// All of the ingredients of the recipe were on the shelf. The `Ate` entries for each
+    /// ingredient follow.
+    MadeRecipe { day: u32, event_index: u32, recipe: String },
+    /// The recipe could not be made because `key`, the first short ingredient, was `grams_short`
+    /// grams short, so nothing was eaten. A `Starved` entry for the whole recipe follows.
+    RecipeIncomplete { day: u32, event_index: u32, recipe: String, key: String, grams_short: u32 },
+    /// The entries up to the next `MealStarted` or `DayEnd` are part of this meal.
+    MealStarted { day: u32, event_index: u32, slot: MealSlot },
+}
+
+pub fn write_entry(mut w: impl Write, entry: &LogEntry) -> std::io::Result<()> {
+    serde_json::to_writer(&mut w, entry)?;
+    writeln!(w)
+}
+
+#[cfg(test)]
+mod log_entry_works {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_a_line() {
+        let entry = LogEntry::Ate {
+            day: 3,
+            event_index: 17,
+            key: "Milk".to_string(),
+            grams: 250,
+            servings: Some(1.),
+            out_grams: 0,
+        };
+
+        let mut line = Vec::new();
+        write_entry(&mut line, &entry).unwrap();
+
+        let line = String::from_utf8(line).unwrap();
+
+        assert_eq!(
+            line,
+            "{\"step\":\"Ate\",\"day\":3,\"event_index\":17,\"key\":\"Milk\",\"grams\":250,\"servings\":1.0,\"out_grams\":0}\n",
+        );
+        assert_eq!(serde_json::from_str::<LogEntry>(&line).unwrap(), entry);
+    }
+
+    #[test]
+    fn round_trips_without_servings() {
+        let entry = LogEntry::Bought {
+            day: 0,
+            event_index: 1,
+            key: "Mystery Meat".to_string(),
+            grams: 500,
+            servings: None,
+            price: 0,
+        };
+
+        let mut line = Vec::new();
+        write_entry(&mut line, &entry).unwrap();
+
+        assert_eq!(serde_json::from_slice::<LogEntry>(&line).unwrap(), entry);
+    }
+}
@@ -0,0 +1,868 @@
+//! The purchase and hunger models that produce the events for each day.
+//!
+//! Each kind of event source is a params struct that implements `EventSource`. The sources that a
+//! config file can refer to by name are the ones in the `Registry` passed to
+//! `config::get_spec_with`. `Registry::default()` has all the sources defined here, and other
+//! crates can add their own with `Registry::register`.
+
+use crate::basic::Food;
+use crate::params_def;
+use crate::types::{food::{self, Grams}, FoodTypes, FullnessThreshold, IndexOffset, Money, Params, RollOnePastMax, ShoppingCount};
+use crate::xs::{self, Xs};
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    Ate(food::Key, Grams),
+    Bought(Food, food::Servings),
+    BuyAllBasedOnFullness(BuyAllBasedOnFullnessParams),
+    /// Eat all of the ingredients, or if any are short, none of them.
+    AteRecipe(food::Recipe),
+    /// The `Ate` events that follow, up to the next `MealStarted` or the end of the day, are
+    /// all part of this meal.
+    MealStarted(MealSlot),
+    /// Not produced by event sources. Passed to `simulate` at the end of each day.
+    DayPassed,
+}
+
+impl Event {
+    pub fn from_rng(food_types: &FoodTypes, rng: &mut Xs) -> Self {
+        match xs::range(rng, 0..2) {
+            1 => Self::Bought(Food::from_rng(food_types, rng), 0),
+            _ => {
+                let food = Food::from_rng(food_types, rng);
+                Self::Ate(food.key().clone(), food.grams())
+            },
+        }
+    }
+}
+
+/// What an event source has to work with when producing the events for a day.
+pub struct EventSourceBundle<'bundle> {
+    pub push_event: &'bundle mut dyn FnMut(Event),
+    pub rng: &'bundle mut Xs,
+    pub food_types: &'bundle FoodTypes,
+    pub recipes: &'bundle [food::Recipe],
+    /// The keys of the foods eaten over the last couple of days.
+    pub recently_eaten_foods: Vec<food::Key>,
+}
+
+pub trait EventSource: EventSourceClone + core::fmt::Debug + Send + Sync {
+    /// The name used to refer to this kind of source, both as the `kind` in config files and in
+    /// target paths.
+    fn name(&self) -> &'static str;
+
+    fn params(&self) -> &dyn Params;
+
+    fn params_mut(&mut self) -> &mut dyn Params;
+
+    /// Returns an error message if the params don't make sense with the given food types and recipes.
+    fn validate(&self, _food_types: &FoodTypes, _recipes: &[food::Recipe]) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Push the events for one day.
+    fn emit(&self, bundle: EventSourceBundle);
+}
+
+/// Lets `EventSourceSpec`s be cloned. Implemented for every `EventSource` that is `Clone`.
+pub trait EventSourceClone {
+    fn clone_box(&self) -> Box<dyn EventSource>;
+}
+
+impl <S: EventSource + Clone + 'static> EventSourceClone for S {
+    fn clone_box(&self) -> Box<dyn EventSource> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn EventSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+type Constructor = fn(serde_json::Value) -> Result<Box<dyn EventSource>, serde_json::Error>;
+
+fn construct<S>(params: serde_json::Value) -> Result<Box<dyn EventSource>, serde_json::Error>
+where
+    S: EventSource + serde::de::DeserializeOwned + 'static
+{
+    Ok(Box::new(serde_json::from_value::<S>(params)?))
+}
+
+/// The kinds of event sources that can be referred to by name.
+#[derive(Clone)]
+pub struct Registry {
+    constructors: Vec<(&'static str, Constructor)>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry
+            .register::<BuyAllBasedOnFullnessParams>()
+            .register::<BuyIfHalfEmptyParams>()
+            .register::<BuyRandomVarietyParams>()
+            .register::<BuyNOfEverythingParams>()
+            .register::<BuyExactlyParams>()
+            .register::<EatExactlyParams>()
+            .register::<FixedHungerAmountParams>()
+            .register::<FixedServingsAmountParams>()
+            .register::<MealsParams>()
+            .register::<RecipesParams>()
+            .register::<ShopSomeDaysParams>()
+            .register::<RandomEventParams>();
+
+        registry
+    }
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Self {
+            constructors: Vec::new(),
+        }
+    }
+
+    /// Makes `S` available under the name of its default value. Any source previously registered
+    /// under that name is replaced.
+    pub fn register<S>(&mut self) -> &mut Self
+    where
+        S: EventSource + Default + serde::de::DeserializeOwned + 'static
+    {
+        let name = S::default().name();
+
+        self.constructors.retain(|(n, _)| *n != name);
+        self.constructors.push((name, construct::<S>));
+
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.constructors.iter().map(|(name, _)| *name)
+    }
+
+    /// Builds the source with the given name out of the given params. Params that the source does
+    /// not have are ignored, and ones that are missing get their default values.
+    /// Returns `None` if there is no source with that name.
+    pub fn build(
+        &self,
+        name: &str,
+        params: serde_json::Value,
+    ) -> Option<Result<Box<dyn EventSource>, serde_json::Error>> {
+        self.constructors.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, constructor)| constructor(params))
+    }
+}
+
+macro_rules! event_source_boilerplate {
+    ($name: literal) => {
+        fn name(&self) -> &'static str {
+            $name
+        }
+
+        fn params(&self) -> &dyn Params {
+            self
+        }
+
+        fn params_mut(&mut self) -> &mut dyn Params {
+            self
+        }
+    }
+}
+
+params_def!{
+    pub struct BuyAllBasedOnFullnessParams {
+        pub max_count: ShoppingCount,
+        pub offset: IndexOffset,
+        pub fullness_threshold: FullnessThreshold,
+        pub minimum_purchase_servings: food::Servings,
+    }
+}
+
+impl EventSource for BuyAllBasedOnFullnessParams {
+    event_source_boilerplate!("BuyIfBelowThreshold");
+
+    fn emit(&self, EventSourceBundle { push_event, .. }: EventSourceBundle) {
+        push_event(Event::BuyAllBasedOnFullness(self.clone()));
+    }
+}
+
+params_def!{
+    pub struct BuyIfHalfEmptyParams {
+        pub max_count: ShoppingCount,
+        pub offset: IndexOffset,
+    }
+}
+
+impl EventSource for BuyIfHalfEmptyParams {
+    event_source_boilerplate!("BuyIfHalfEmpty");
+
+    fn emit(&self, EventSourceBundle { push_event, .. }: EventSourceBundle) {
+        push_event(Event::BuyAllBasedOnFullness(BuyAllBasedOnFullnessParams {
+            max_count: self.max_count,
+            offset: self.offset,
+            fullness_threshold: 0.5,
+            minimum_purchase_servings: 0,
+        }));
+    }
+}
+
+params_def!{
+    pub struct BuyRandomVarietyParams {
+        pub count: ShoppingCount,
+        pub offset: IndexOffset,
+    }
+}
+
+impl EventSource for BuyRandomVarietyParams {
+    event_source_boilerplate!("BuyRandomVariety");
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            food_types,
+            ..
+        }: EventSourceBundle,
+    ) {
+        let Self { count, offset } = self;
+
+        for i in 0..*count {
+            let index = (i as usize).wrapping_add(*offset) % food_types.len();
+            push_event(Event::Bought(
+                Food::from_rng_of_type(&food_types[index], rng),
+                0,
+            ));
+        }
+    }
+}
+
+// TODO hunger model, or modifier over all hunger models that tracks what was eaten recently and avoids
+//      eating that for a while. Maybe pass down a filtered food_types?
+
+// TODO? Label certain foods as once-per-day? Or maybe some generalization of that, like n times per period
+
+// TODO purchase model that buys enough that we have n servings of everything
+
+/// The weight of each food type, with any of the given overrides applied.
+fn weights_of(food_types: &[&food::Type], overrides: &[(food::Key, f32)]) -> Vec<f32> {
+    food_types.iter()
+        .map(|type_| {
+            overrides.iter()
+                .find(|(key, _)| key == &type_.key)
+                .map_or(type_.weight, |&(_, weight)| weight)
+        })
+        .collect()
+}
+
+/// An error message if any of the overrides are for unknown keys, or are not valid weights.
+fn check_weights(overrides: &[(food::Key, f32)], food_types: &FoodTypes) -> Result<(), String> {
+    for (key, weight) in overrides {
+        check_key("weights key", key, food_types)?;
+
+        if !(weight.is_finite() && *weight >= 0.) {
+            return Err(format!("The weight for \"{key}\" must be a non-negative number, not {weight}"));
+        }
+    }
+
+    Ok(())
+}
+
+params_def!{
+    pub struct FixedServingsAmountParams {
+        pub servings_per_day: food::Servings,
+        /// Used instead of the weights of the food types with these keys.
+        pub weights: Vec<(food::Key, f32)>,
+    }
+}
+
+impl EventSource for FixedServingsAmountParams {
+    event_source_boilerplate!("FixedServingsAmount");
+
+    fn validate(&self, food_types: &FoodTypes, _recipes: &[food::Recipe]) -> Result<(), String> {
+        check_weights(&self.weights, food_types)
+    }
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            food_types: full_food_types,
+            recently_eaten_foods,
+            ..
+        }: EventSourceBundle,
+    ) {
+        let food_types =
+            full_food_types.iter()
+                .filter(|t| !recently_eaten_foods.contains(&t.key))
+                .collect::<Vec<_>>()
+            ;
+
+        let weights = weights_of(&food_types, &self.weights);
+
+        let mut g_state = xs::GaussianState::default();
+
+        let mut servings_remaining = self.servings_per_day as f32;
+        while servings_remaining > 0. {
+            // Every food left was eaten recently or has a weight of 0, so none of them should be
+            // picked. The weights don't change, so the rest of the day goes without.
+            let Some(index) = xs::weighted_index(rng, &weights) else {
+                break
+            };
+
+            let type_ = &food_types[index];
+
+            // At least one serving, and as much as four.
+            let servings_count = 1. + xs::gaussian_zero_to_one(rng, &mut g_state) * 3.;
+
+            let serving_grams = type_.serving.get();
+
+            let amount =
+                (servings_count * serving_grams as f32) as food::Grams;
+
+            push_event(Event::Ate(
+                type_.key.clone(),
+                amount,
+            ));
+
+            servings_remaining -= servings_count;
+        }
+    }
+}
+
+/// When in the day a meal is eaten. Meals are eaten in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub enum MealSlot {
+    Breakfast,
+    Lunch,
+    Dinner,
+    Snack,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Meal {
+    pub slot: MealSlot,
+    /// How many servings are eaten, in total, across all the foods in the meal.
+    pub servings: food::Servings,
+    /// The foods that can be part of the meal. If empty, any food can be.
+    #[serde(default)]
+    pub keys: Vec<food::Key>,
+}
+
+params_def!{
+    pub struct MealsParams {
+        pub meals: Vec<Meal>,
+    }
+}
+
+impl EventSource for MealsParams {
+    event_source_boilerplate!("Meals");
+
+    fn validate(&self, food_types: &FoodTypes, _recipes: &[food::Recipe]) -> Result<(), String> {
+        for meal in &self.meals {
+            for key in &meal.keys {
+                check_key(&format!("{:?} key", meal.slot), key, food_types)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            food_types,
+            ..
+        }: EventSourceBundle,
+    ) {
+        let mut meals = self.meals.iter().collect::<Vec<_>>();
+        meals.sort_by_key(|meal| meal.slot);
+
+        for meal in meals {
+            let mut allowed =
+                food_types.iter()
+                    .filter(|t| meal.keys.is_empty() || meal.keys.contains(&t.key))
+                    .collect::<Vec<_>>()
+                ;
+
+            if allowed.is_empty() {
+                continue
+            }
+
+            push_event(Event::MealStarted(meal.slot));
+
+            // Go around the foods in a random order, a serving at a time, so a meal with
+            // more servings than foods has some of everything.
+            xs::shuffle(rng, &mut allowed);
+
+            let mut amounts: Vec<Grams> = vec![0; allowed.len()];
+            for i in 0..meal.servings as usize {
+                let index = i % allowed.len();
+                amounts[index] = amounts[index].saturating_add(allowed[index].serving.get());
+            }
+
+            for (type_, amount) in allowed.into_iter().zip(amounts) {
+                if amount > 0 {
+                    push_event(Event::Ate(
+                        type_.key.clone(),
+                        amount,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+params_def!{
+    pub struct RecipesParams {
+        pub recipes_per_day: u8,
+        /// The names of the recipes to pick from. If empty, any recipe can be picked.
+        pub names: Vec<String>,
+    }
+}
+
+impl EventSource for RecipesParams {
+    event_source_boilerplate!("Recipes");
+
+    fn validate(&self, _food_types: &FoodTypes, recipes: &[food::Recipe]) -> Result<(), String> {
+        for name in &self.names {
+            if !recipes.iter().any(|recipe| &recipe.name == name) {
+                return Err(format!("\"{name}\" is not the name of one of the recipes"));
+            }
+        }
+
+        if recipes.is_empty() && self.recipes_per_day > 0 {
+            return Err("There are no recipes to pick from".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            recipes,
+            ..
+        }: EventSourceBundle,
+    ) {
+        let recipes =
+            recipes.iter()
+                .filter(|r| self.names.is_empty() || self.names.contains(&r.name))
+                .collect::<Vec<_>>()
+            ;
+
+        if recipes.is_empty() {
+            return
+        }
+
+        for _ in 0..self.recipes_per_day {
+            let index = xs::range(rng, 0..recipes.len() as u32) as usize;
+
+            push_event(Event::AteRecipe(recipes[index].clone()));
+        }
+    }
+}
+
+/// An error message if `key` is not the key of one of the food types.
+fn check_key(key_name: &str, key: &food::Key, food_types: &FoodTypes) -> Result<(), String> {
+    if food_types.iter().any(|type_| &type_.key == key) {
+        Ok(())
+    } else {
+        Err(format!("{key_name} \"{key}\" is not one of the food_types keys"))
+    }
+}
+
+params_def!{
+    pub struct EatExactlyParams {
+        pub key_to_eat: food::Key,
+        pub grams_to_eat: food::Grams,
+    }
+}
+
+impl EventSource for EatExactlyParams {
+    event_source_boilerplate!("EatExactly");
+
+    fn validate(&self, food_types: &FoodTypes, _recipes: &[food::Recipe]) -> Result<(), String> {
+        check_key("key_to_eat", &self.key_to_eat, food_types)
+    }
+
+    fn emit(&self, EventSourceBundle { push_event, .. }: EventSourceBundle) {
+        push_event(Event::Ate(
+            self.key_to_eat.clone(),
+            self.grams_to_eat,
+        ));
+    }
+}
+
+params_def!{
+    pub struct BuyExactlyParams {
+        pub key_to_buy: food::Key,
+        pub grams_to_buy: food::Grams,
+    }
+}
+
+impl EventSource for BuyExactlyParams {
+    event_source_boilerplate!("BuyExactly");
+
+    fn validate(&self, food_types: &FoodTypes, _recipes: &[food::Recipe]) -> Result<(), String> {
+        check_key("key_to_buy", &self.key_to_buy, food_types)
+    }
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            food_types,
+            ..
+        }: EventSourceBundle,
+    ) {
+        let Self { key_to_buy, grams_to_buy } = self;
+
+        let type_ = food_types.iter().find(|type_| &type_.key == key_to_buy);
+
+        // Use the price of a matching option if there is one, otherwise go by the price per gram
+        // of the first option.
+        let price = type_.map(|type_| {
+            match type_.options.iter().find(|option| option.grams == *grams_to_buy) {
+                Some(option) => option.price,
+                None => {
+                    let first = type_.options.first();
+
+                    (first.price as u64 * *grams_to_buy as u64 / core::cmp::max(first.grams, 1) as u64) as Money
+                }
+            }
+        }).unwrap_or_default();
+
+        push_event(Event::Bought(
+            Food::of_key(
+                key_to_buy.clone(),
+                food::Option {
+                    grams: *grams_to_buy,
+                    price,
+                    shelf_life: type_.and_then(|type_| type_.shelf_life),
+                    opened_shelf_life: type_.and_then(|type_| type_.opened_shelf_life),
+                }
+            ),
+            0,
+        ));
+    }
+}
+
+params_def!{
+    pub struct FixedHungerAmountParams {
+        pub grams_per_day: food::Grams,
+        /// Used instead of the weights of the food types with these keys.
+        pub weights: Vec<(food::Key, f32)>,
+    }
+}
+
+impl EventSource for FixedHungerAmountParams {
+    event_source_boilerplate!("FixedHungerAmount");
+
+    fn validate(&self, food_types: &FoodTypes, _recipes: &[food::Recipe]) -> Result<(), String> {
+        check_weights(&self.weights, food_types)
+    }
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            food_types: full_food_types,
+            recently_eaten_foods,
+            ..
+        }: EventSourceBundle,
+    ) {
+        let food_types =
+            full_food_types.iter()
+                .filter(|t| !recently_eaten_foods.contains(&t.key))
+                .collect::<Vec<_>>()
+            ;
+
+        let weights = weights_of(&food_types, &self.weights);
+
+        let mut grams_remaining = self.grams_per_day;
+        while grams_remaining > 0 {
+            // See `FixedServingsAmountParams::emit`.
+            let Some(index) = xs::weighted_index(rng, &weights) else {
+                break
+            };
+
+            let type_ = &food_types[index];
+
+            let amount = xs::range(rng, 1..(grams_remaining as u32 + 1)) as Grams;
+
+            push_event(Event::Ate(
+                type_.key.clone(),
+                amount,
+            ));
+
+            grams_remaining = grams_remaining.saturating_sub(amount as _);
+        }
+    }
+}
+
+params_def!{
+    pub struct BuyNOfEverythingParams {
+        pub n: ShoppingCount,
+    }
+}
+
+impl EventSource for BuyNOfEverythingParams {
+    event_source_boilerplate!("BuyNOfEverything");
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            food_types,
+            ..
+        }: EventSourceBundle,
+    ) {
+        for type_ in food_types {
+            for _ in 0..self.n {
+                push_event(Event::Bought(
+                    Food::of_type(type_, type_.options[0].clone()),
+                    0,
+                ));
+            }
+        }
+    }
+}
+
+params_def!{
+    pub struct ShopSomeDaysParams {
+        pub buy_count: u8,
+        pub roll_one_past_max: RollOnePastMax,
+    }
+}
+
+impl EventSource for ShopSomeDaysParams {
+    event_source_boilerplate!("ShopSomeDays");
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            food_types,
+            ..
+        }: EventSourceBundle,
+    ) {
+        match xs::range(rng, 0..self.roll_one_past_max.u32()) {
+            0 => {
+                // Go shopping
+                // TODO Count grams and buy a set amount of grams instead of an item count?
+                for _ in 0..self.buy_count {
+                    push_event(Event::Bought(
+                        Food::from_rng(food_types, rng),
+                        0,
+                    ));
+                }
+            },
+            _ => {
+                // Skip shopping
+            }
+        }
+    }
+}
+
+params_def!{
+    pub struct RandomEventParams {
+        pub roll_one_past_max: RollOnePastMax,
+    }
+}
+
+impl EventSource for RandomEventParams {
+    event_source_boilerplate!("RandomEvent");
+
+    fn emit(
+        &self,
+        EventSourceBundle {
+            push_event,
+            rng,
+            food_types,
+            ..
+        }: EventSourceBundle,
+    ) {
+        // Have random things happen sometimes as an attempt to capture things not explicitly modeled
+        if xs::range(rng, 0..self.roll_one_past_max.u32()) == 0 {
+            push_event(Event::from_rng(food_types, rng));
+        }
+    }
+}
+
+#[cfg(test)]
+mod registry_works {
+    use super::*;
+
+    #[test]
+    fn on_built_in_sources() {
+        let registry = Registry::default();
+
+        assert_eq!(registry.names().count(), 12);
+
+        let source = registry.build(
+            "ShopSomeDays",
+            serde_json::json!({ "buy_count": 3, "roll_one_past_max": 4, "unrelated": 1 }),
+        ).unwrap().unwrap();
+
+        assert_eq!(source.name(), "ShopSomeDays");
+        assert_eq!(source.params().get("buy_count"), Some(3.));
+        assert_eq!(source.params().get("roll_one_past_max"), Some(4.));
+
+        assert!(registry.build("NotASource", serde_json::json!({})).is_none());
+        assert!(registry.build("ShopSomeDays", serde_json::json!({ "buy_count": "lots" })).unwrap().is_err());
+    }
+
+    #[test]
+    fn on_a_source_from_elsewhere() {
+        params_def!{
+            pub struct EatTheSameThingParams {
+                pub grams: food::Grams,
+            }
+        }
+
+        impl EventSource for EatTheSameThingParams {
+            fn name(&self) -> &'static str {
+                "EatTheSameThing"
+            }
+
+            fn params(&self) -> &dyn Params {
+                self
+            }
+
+            fn params_mut(&mut self) -> &mut dyn Params {
+                self
+            }
+
+            fn emit(&self, EventSourceBundle { push_event, food_types, .. }: EventSourceBundle) {
+                push_event(Event::Ate(food_types.first().key.clone(), self.grams));
+            }
+        }
+
+        let mut registry = Registry::default();
+        registry.register::<EatTheSameThingParams>();
+
+        assert!(registry.names().any(|name| name == "EatTheSameThing"));
+
+        let source = registry.build("EatTheSameThing", serde_json::json!({ "grams": 5 }))
+            .unwrap()
+            .unwrap();
+
+        let food_types: FoodTypes = vec1::vec1![food::Type::new("key", food::Option::default())];
+        let mut rng = xs::from_seed([1; 16]);
+        let mut events = Vec::new();
+
+        source.emit(EventSourceBundle {
+            push_event: &mut |event| events.push(event),
+            rng: &mut rng,
+            food_types: &food_types,
+            recipes: &[],
+            recently_eaten_foods: Vec::new(),
+        });
+
+        assert!(matches!(&events[..], [Event::Ate(key, 5)] if key == "key"));
+    }
+}
+
+#[cfg(test)]
+mod meals_works {
+    use super::*;
+
+    #[test]
+    fn in_slot_order_with_the_servings_spread_out() {
+        let food_type = |key: &str| food::Type {
+            serving: food::NonZeroGrams::new(10).unwrap(),
+            ..food::Type::new(key, food::Option::default())
+        };
+        let food_types: FoodTypes = vec1::vec1![food_type("Cereal"), food_type("Milk"), food_type("Ramen")];
+
+        let meal = |slot, servings, keys: &[&str]| Meal {
+            slot,
+            servings,
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        };
+
+        let params = MealsParams {
+            meals: vec![
+                meal(MealSlot::Dinner, 1, &["Ramen"]),
+                meal(MealSlot::Breakfast, 3, &["Cereal", "Milk"]),
+            ],
+        };
+
+        assert!(params.validate(&food_types, &[]).is_ok());
+
+        let mut rng = xs::from_seed([1; 16]);
+        let mut events = Vec::new();
+
+        params.emit(EventSourceBundle {
+            push_event: &mut |event| events.push(event),
+            rng: &mut rng,
+            food_types: &food_types,
+            recipes: &[],
+            recently_eaten_foods: Vec::new(),
+        });
+
+        assert!(matches!(events[0], Event::MealStarted(MealSlot::Breakfast)));
+        assert!(matches!(events[3], Event::MealStarted(MealSlot::Dinner)));
+
+        let eaten = events.iter()
+            .filter_map(|event| match event {
+                Event::Ate(key, grams) => Some((key.as_str(), *grams)),
+                Event::MealStarted(_) => None,
+                _ => panic!("Expected only meal and Ate events, got {event:?}"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(eaten.len(), 3);
+        assert_eq!(eaten.iter().take(2).map(|(_, grams)| grams).sum::<Grams>(), 30);
+        assert!(eaten[..2].iter().all(|(key, grams)| ["Cereal", "Milk"].contains(key) && *grams >= 10));
+        assert_eq!(eaten[2], ("Ramen", 10));
+
+        let bad = MealsParams {
+            meals: vec![meal(MealSlot::Lunch, 1, &["Sandwich"])],
+        };
+        assert!(bad.validate(&food_types, &[]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod fixed_hunger_amount_works {
+    use super::*;
+
+    #[test]
+    fn on_only_zero_weight_foods_left() {
+        let food_type = |key: &str| food::Type::new(key, food::Option::default());
+        let food_types: FoodTypes = vec1::vec1![food_type("Liver"), food_type("Ramen")];
+
+        let params = FixedHungerAmountParams {
+            grams_per_day: 100,
+            weights: vec![("Liver".to_string(), 0.)],
+        };
+
+        assert!(params.validate(&food_types, &[]).is_ok());
+
+        let mut rng = xs::from_seed([1; 16]);
+        let mut events = Vec::new();
+
+        params.emit(EventSourceBundle {
+            push_event: &mut |event| events.push(event),
+            rng: &mut rng,
+            food_types: &food_types,
+            recipes: &[],
+            recently_eaten_foods: vec!["Ramen".to_string()],
+        });
+
+        assert!(events.is_empty(), "{events:?}");
+    }
+}
@@ -0,0 +1,24 @@
+//! A simulator of a household's food inventory, for comparing purchasing strategies.
+//!
+//! Build a `types::Spec`, either directly or with `config::get_spec`, then either call
+//! `basic::run`, or step through a `basic::Simulation` day by day. The other Basic modes are run
+//! with `search::run`, `print_calls::run` and `batch::run`.
+
+pub mod xs;
+pub mod minimize;
+pub mod aggregate;
+pub mod parallel;
+pub mod output;
+pub mod svg;
+pub mod event_log;
+pub mod event_source;
+pub mod types;
+pub mod config;
+pub mod minimal;
+pub mod basic;
+pub mod search;
+pub mod print_calls;
+pub mod batch;
+
+pub use basic::{RunOutput, Simulation};
+pub use types::{Mode, Res, Spec};
@@ -0,0 +1,25 @@
+use std::io::Write;
+use crate::types::Spec;
+use crate::xs;
+
+struct GummyBear;
+
+type Minimal = Option<GummyBear>;
+
+pub fn run(spec: &Spec, mut w: impl Write) -> Result<(), std::io::Error> {
+    let mut rng = xs::from_seed(spec.seed.unwrap_or_default());
+
+    let mut study: Minimal = if xs::range(&mut rng, 0..2) > 0 { Some(GummyBear) } else { None };
+
+    writeln!(w, "{}", study.is_some())?;
+
+    study = Some(GummyBear);
+
+    writeln!(w, "{}", study.is_some())?;
+
+    study = None;
+
+    writeln!(w, "{}", study.is_some())?;
+
+    Ok(())
+}
@@ -1,5 +1,10 @@
+// The maths here reads more clearly with indices into the fixed size arrays.
+#![allow(clippy::needless_range_loop)]
+
 use std::ops::{Index, IndexMut};
 
+use crate::xs::{self, Xs};
+
 type X = f32;
 type Y = f32;
 
@@ -18,7 +23,7 @@ pub const TWO_D_ZERO: Call<1> = Call {
 
 /// A workaround for the lack of `generic_const_exprs` on stable, which would be needed to express
 /// `[[X; N]; N + 1]`.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Simplex<const N: usize> {
     pub n: [[X; N]; N],
     pub plus_one: [X; N],
@@ -49,6 +54,8 @@ impl <const N: usize> IndexMut<usize> for Simplex<N> {
 }
 
 impl <const N: usize> Simplex<N> {
+    // A simplex always has at least one vertex, so `is_empty` would always be false.
+    #[allow(clippy::len_without_is_empty)]
     pub const fn len(&self) -> usize {
         N + 1
     }
@@ -133,30 +140,109 @@ mod regular_simplex_works {
 pub fn regular_simplex_centered_at<const N: usize>(
     scale: X,
     center: [X; N]
+) -> Simplex<N> {
+    simplex_centered_at([scale; N], center)
+}
+
+/// A regular simplex stretched along each axis by the corresponding scale, for when the axes have
+/// different units.
+pub fn simplex_centered_at<const N: usize>(
+    scales: [X; N],
+    center: [X; N]
 ) -> Simplex<N> {
     let mut output = regular_simplex::<N>();
 
     for vertex_index in 0..output.len() {
         for i in 0..N {
             output[vertex_index][i] =
-                output[vertex_index][i] * scale + center[i];
+                output[vertex_index][i] * scales[i] + center[i];
         }
     }
 
     output
 }
 
+impl <const N: usize> Simplex<N> {
+    /// The average of the vertices.
+    pub fn center(&self) -> [X; N] {
+        let mut output = [0.; N];
+        for vertex_index in 0..self.len() {
+            for i in 0..N {
+                output[i] += self[vertex_index][i];
+            }
+        }
+        for i in 0..N {
+            output[i] /= self.len() as X;
+        }
+        output
+    }
+
+    /// The same simplex, moved so that its center is at `center`.
+    pub fn moved_to(mut self, center: [X; N]) -> Self {
+        let current = self.center();
+        for vertex_index in 0..self.len() {
+            for i in 0..N {
+                self[vertex_index][i] += center[i] - current[i];
+            }
+        }
+        self
+    }
+}
+
+/// When to stop minimizing.
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    // 64k iterations ought to be enough for anybody!
+    pub max_iterations: u16,
+    /// Stop once every vertex of the simplex is at most this far from the best one, and ...
+    pub x_tolerance: X,
+    /// ... every vertex's value is at most this much larger than the best one.
+    pub y_tolerance: Y,
+}
+
+impl Options {
+    /// Only stop after `max_iterations`, or once the simplex has collapsed to a single point.
+    #[allow(unused)]
+    pub fn iterations(max_iterations: u16) -> Self {
+        Self {
+            max_iterations,
+            x_tolerance: 0.,
+            y_tolerance: 0.,
+        }
+    }
+}
+
+/// The state at the start of one iteration of `minimize`.
+#[derive(Clone, Copy, Debug)]
+pub struct Iteration<const N: usize> {
+    pub index: u16,
+    pub best: Call<N>,
+    /// The largest distance from the best vertex to another vertex.
+    pub diameter: X,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Minimized<const N: usize> {
+    pub call: Call<N>,
+    pub iterations: u16,
+    /// Each iteration can evaluate several points, so this is wider than `iterations`.
+    pub evaluations: u32,
+    /// Whether we stopped because the tolerances were met, rather than by running out of iterations.
+    pub converged: bool,
+}
+
 /// Find the minimum of the given function withing the given simplex.
 /// If in doubt of what to use for the simplex, pass
 /// `regular_simplex_centered_at(scale, center)` where `center` is a
 /// best guess for the minimum, and scale is large enough that the
 /// resulting simplex covers the desired minimum.
+/// `on_iteration` is called at the start of each iteration, for tracing progress.
 pub fn minimize<const N: usize>(
     f: impl Fn([X; N]) -> Y,
     initial_simplex: Simplex<N>,
-    // 64k iterations ought to be enough for anybody!
-    iters: u16,
-) -> Call<N> {
+    Options { max_iterations, x_tolerance, y_tolerance }: Options,
+    mut on_iteration: impl FnMut(Iteration<N>),
+) -> Minimized<N> {
     // Nelder–Mead method
     // References used:
     // Wikipedia Article: https://en.wikipedia.org/wiki/Nelder%E2%80%93Mead_method
@@ -170,6 +256,12 @@ pub fn minimize<const N: usize>(
 
     let mut k = 0;
 
+    let evaluations = core::cell::Cell::new(0);
+    let f = |xs| {
+        evaluations.set(evaluations.get() + 1);
+        f(xs)
+    };
+
     // TODO? Do this on the stack?
     let mut s = Vec::with_capacity(N + 1);
     for i in 0..(N + 1) {
@@ -177,10 +269,36 @@ pub fn minimize<const N: usize>(
         s.push(Call { xs, y: f(xs) });
     }
 
-
-    while k < iters {
-        // Order
+    let order = |s: &mut Vec<Call<N>>| {
         s.sort_by(|a, b| a.y.partial_cmp(&b.y).expect("should have no NaNs"));
+    };
+
+    let mut converged = false;
+
+    while k < max_iterations {
+        order(&mut s);
+
+        let diameter = s.iter()
+            .map(|call| {
+                let mut sum = 0.;
+                for i in 0..N {
+                    let d = call.xs[i] - s[0].xs[i];
+                    sum += d * d;
+                }
+                sum.sqrt()
+            })
+            .fold(0., X::max);
+
+        on_iteration(Iteration {
+            index: k,
+            best: s[0],
+            diameter,
+        });
+
+        if diameter <= x_tolerance && s[N].y - s[0].y <= y_tolerance {
+            converged = true;
+            break
+        }
 
         let l_k = 0;
         let h_k = s.len() - 1;
@@ -268,31 +386,372 @@ pub fn minimize<const N: usize>(
         k += 1;
     }
 
-    s[0]
+    order(&mut s);
+
+    Minimized {
+        call: s[0],
+        iterations: k,
+        evaluations: evaluations.get(),
+        converged,
+    }
+}
+
+/// An axis-aligned box to search within.
+#[derive(Clone, Copy, Debug)]
+pub struct Region<const N: usize> {
+    pub lower: [X; N],
+    pub upper: [X; N],
+}
+
+impl <const N: usize> Region<N> {
+    pub fn center(&self) -> [X; N] {
+        let mut output = [0.; N];
+        for i in 0..N {
+            output[i] = (self.lower[i] + self.upper[i]) * 0.5;
+        }
+        output
+    }
+
+    pub fn clamp(&self, mut xs: [X; N]) -> [X; N] {
+        for i in 0..N {
+            xs[i] = xs[i].max(self.lower[i]).min(self.upper[i]);
+        }
+        xs
+    }
+
+    fn random_point(&self, rng: &mut Xs) -> [X; N] {
+        let mut output = [0.; N];
+        for i in 0..N {
+            output[i] = self.lower[i] + (self.upper[i] - self.lower[i]) * xs::zero_to_one(rng);
+        }
+        output
+    }
+}
+
+fn distance<const N: usize>(a: [X; N], b: [X; N]) -> X {
+    let mut sum = 0.;
+    for i in 0..N {
+        let d = a[i] - b[i];
+        sum += d * d;
+    }
+    sum.sqrt()
+}
+
+/// Evaluate the function at every point of a grid covering the region, with `steps` points along
+/// each axis, and return the lowest. `on_iteration` is called once per point, with the best so far.
+pub fn grid_search<const N: usize>(
+    f: impl Fn([X; N]) -> Y,
+    region: Region<N>,
+    steps: u16,
+    mut on_iteration: impl FnMut(Iteration<N>),
+) -> Minimized<N> {
+    let steps = core::cmp::max(steps, 1);
+
+    let point = |indices: &[u16; N]| {
+        if steps == 1 {
+            return region.center()
+        }
+
+        let mut xs = [0.; N];
+        for i in 0..N {
+            let t = indices[i] as X / (steps - 1) as X;
+            xs[i] = region.lower[i] + (region.upper[i] - region.lower[i]) * t;
+        }
+        xs
+    };
+
+    let cell_size = {
+        let mut cell = region.lower;
+        for i in 0..N {
+            cell[i] += (region.upper[i] - region.lower[i]) / core::cmp::max(steps - 1, 1) as X;
+        }
+        distance(region.lower, cell)
+    };
+
+    let mut indices = [0; N];
+    let mut best: Option<Call<N>> = None;
+    let mut evaluations: u32 = 0;
+    let mut k: u16 = 0;
+
+    loop {
+        let xs = point(&indices);
+        let y = f(xs);
+        evaluations += 1;
+
+        if best.is_none_or(|best| y < best.y) {
+            best = Some(Call { xs, y });
+        }
+
+        if let Some(best) = best {
+            on_iteration(Iteration {
+                index: k,
+                best,
+                diameter: cell_size,
+            });
+        }
+        k = k.saturating_add(1);
+
+        // Advance to the next point, like an odometer.
+        let mut i = 0;
+        loop {
+            if i >= N {
+                return Minimized {
+                    call: best.expect("should have evaluated at least one point"),
+                    iterations: k,
+                    evaluations,
+                    converged: true,
+                }
+            }
+
+            indices[i] += 1;
+            if indices[i] < steps {
+                break
+            }
+            indices[i] = 0;
+            i += 1;
+        }
+    }
+}
+
+/// Run `minimize` starting from the given simplex, then again `restarts` more times, starting from
+/// the same simplex moved to a random point in the region, and return the best result.
+pub fn random_restarts<const N: usize>(
+    f: impl Fn([X; N]) -> Y,
+    initial_simplex: Simplex<N>,
+    region: Region<N>,
+    restarts: u16,
+    options: Options,
+    rng: &mut Xs,
+    mut on_iteration: impl FnMut(Iteration<N>),
+) -> Minimized<N> {
+    let mut best = minimize(&f, initial_simplex, options, &mut on_iteration);
+
+    for _ in 0..restarts {
+        let simplex = initial_simplex.moved_to(region.random_point(rng));
+
+        let result = minimize(&f, simplex, options, &mut on_iteration);
+
+        best = Minimized {
+            call: if result.call.y < best.call.y { result.call } else { best.call },
+            iterations: best.iterations.saturating_add(result.iterations),
+            evaluations: best.evaluations.saturating_add(result.evaluations),
+            converged: best.converged && result.converged,
+        };
+    }
+
+    best
+}
+
+/// A simple evolution strategy: each generation, sample `population` points from a normal
+/// distribution around the current mean, then move the mean to the average of the better half.
+/// The spread grows while the generations keep improving on the best so far, and shrinks otherwise.
+/// Each generation counts as one iteration.
+pub fn evolve<const N: usize>(
+    f: impl Fn([X; N]) -> Y,
+    region: Region<N>,
+    population: u16,
+    Options { max_iterations, x_tolerance, y_tolerance }: Options,
+    rng: &mut Xs,
+    mut on_iteration: impl FnMut(Iteration<N>),
+) -> Minimized<N> {
+    const GROWTH: X = 1.2;
+    const SHRINKAGE: X = 0.8;
+
+    let population = core::cmp::max(population, 2) as usize;
+    let parent_count = population / 2;
+
+    let mut mean = region.center();
+    let mut sigma = [0.; N];
+    for i in 0..N {
+        sigma[i] = (region.upper[i] - region.lower[i]) / 4.;
+    }
+
+    let mut g_state = xs::GaussianState::default();
+
+    let mut best = Call { xs: mean, y: f(mean) };
+    let mut evaluations: u32 = 1;
+
+    let mut k = 0;
+    let mut converged = false;
+
+    let mut generation = Vec::with_capacity(population);
+
+    while k < max_iterations {
+        generation.clear();
+
+        for _ in 0..population {
+            let mut xs = mean;
+            for i in 0..N {
+                xs[i] += sigma[i] * xs::gaussian(rng, &mut g_state);
+            }
+            let xs = region.clamp(xs);
+
+            generation.push(Call { xs, y: f(xs) });
+            evaluations += 1;
+        }
+
+        generation.sort_by(|a, b| a.y.partial_cmp(&b.y).expect("should have no NaNs"));
+
+        let scale = if generation[0].y < best.y {
+            best = generation[0];
+            GROWTH
+        } else {
+            SHRINKAGE
+        };
+
+        let mut new_mean = [0.; N];
+        for call in &generation[..parent_count] {
+            for i in 0..N {
+                new_mean[i] += call.xs[i] / parent_count as X;
+            }
+        }
+        mean = new_mean;
+
+        for i in 0..N {
+            sigma[i] *= scale;
+        }
+
+        let diameter = distance(sigma, [0.; N]);
+
+        on_iteration(Iteration {
+            index: k,
+            best,
+            diameter,
+        });
+
+        k += 1;
+
+        if diameter <= x_tolerance && generation[parent_count - 1].y - generation[0].y <= y_tolerance {
+            converged = true;
+            break
+        }
+    }
+
+    Minimized {
+        call: best,
+        iterations: k,
+        evaluations,
+        converged,
+    }
+}
+
+#[cfg(test)]
+mod global_minimizers_work {
+    use super::*;
+
+    /// Has a local minimum at -1 that is worse than the global one at 2.
+    fn two_dips([x]: [X; 1]) -> Y {
+        ((x + 1.) * (x + 1.)).min((x - 2.) * (x - 2.) - 1.)
+    }
+
+    const REGION: Region<1> = Region {
+        lower: [-4.],
+        upper: [4.],
+    };
+
+    #[test]
+    fn grid_search_finds_the_global_minimum() {
+        let minimized = grid_search(two_dips, REGION, 9, |_| {});
+
+        assert_eq!(minimized.call, Call { xs: [2.], y: -1. });
+        assert_eq!(minimized.evaluations, 9);
+    }
+
+    #[test]
+    fn random_restarts_does_no_worse_than_one_start() {
+        // Starts near the worse dip
+        let simplex = regular_simplex_centered_at(0.5, [-1.5]);
+
+        let single = minimize(two_dips, simplex, Options::iterations(100), |_| {});
+
+        let mut rng = xs::from_seed([1; 16]);
+
+        let minimized = random_restarts(
+            two_dips,
+            simplex,
+            REGION,
+            8,
+            Options::iterations(100),
+            &mut rng,
+            |_| {},
+        );
+
+        assert!(minimized.call.y <= single.call.y, "{minimized:?}");
+        assert!(minimized.evaluations > single.evaluations);
+    }
+
+    #[test]
+    fn evolve_finds_the_global_minimum() {
+        let mut rng = xs::from_seed([1; 16]);
+
+        let minimized = evolve(
+            two_dips,
+            REGION,
+            16,
+            Options::iterations(100),
+            &mut rng,
+            |_| {},
+        );
+
+        assert!((minimized.call.xs[0] - 2.).abs() < 0.01, "{minimized:?}");
+    }
 }
 
 #[cfg(test)]
 mod minimize_works {
     use super::*;
 
+    fn min_x_squared(simplex: Simplex<1>, options: Options) -> Minimized<1> {
+        minimize::<1>(|[x]| x * x, simplex, options, |_| {})
+    }
+
     #[test]
     fn on_x_squared() {
         // Start on the answer
         assert_eq!(
-            minimize::<1>(|[x]| x * x, regular_simplex_centered_at(1.0, [0.0]), 100),
+            min_x_squared(regular_simplex_centered_at(1.0, [0.0]), Options::iterations(100)).call,
             TWO_D_ZERO,
         );
 
         // Start such that inital simplex contains the answer
         assert_eq!(
-            minimize::<1>(|[x]| x * x, regular_simplex_centered_at(2.0, [1.0]), 100),
+            min_x_squared(regular_simplex_centered_at(2.0, [1.0]), Options::iterations(100)).call,
             TWO_D_ZERO,
         );
 
         // Start further away
         assert_eq!(
-            minimize::<1>(|[x]| x * x, regular_simplex_centered_at(4.0, [-2.0]), 100),
+            min_x_squared(regular_simplex_centered_at(4.0, [-2.0]), Options::iterations(100)).call,
             TWO_D_ZERO,
         );
     }
+
+    #[test]
+    fn stops_within_tolerance() {
+        let options = Options {
+            max_iterations: 100,
+            x_tolerance: 0.01,
+            y_tolerance: 0.01,
+        };
+
+        let minimized = min_x_squared(regular_simplex_centered_at(4.0, [-2.0]), options);
+
+        assert!(minimized.converged);
+        assert!(minimized.iterations < 100);
+        assert!(minimized.call.xs[0].abs() < 0.1);
+
+        let mut count = 0;
+        minimize::<1>(
+            |[x]| x * x,
+            regular_simplex_centered_at(4.0, [-2.0]),
+            options,
+            |iteration| {
+                assert_eq!(iteration.index, count);
+                count += 1;
+            }
+        );
+
+        // The iteration that converged is reported too.
+        assert_eq!(count, minimized.iterations + 1);
+    }
 }
\ No newline at end of file
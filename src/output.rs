@@ -0,0 +1,185 @@
+use crate::types::{OutputFormat, Seed};
+
+use std::io::Write;
+
+/// One evaluation of the performance at one value of a target, with the breakdown of the performance.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CallRow {
+    pub target: String,
+    pub x: f32,
+    pub seed: Seed,
+    pub performance: f32,
+    pub starved: f32,
+    pub out: f32,
+    pub waste: f32,
+    pub spend: f32,
+}
+
+const CALL_ROW_COLUMNS: [&str; 8] = ["target", "x", "seed", "performance", "starved", "out", "waste", "spend"];
+
+/// Quotes the field if it would otherwise be misread by a CSV parser.
+pub fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// Writes a JSON array with one row per line, which is still readable, and easy to diff.
+fn write_json_lines<A: serde::Serialize>(rows: &[A], mut w: impl Write) -> std::io::Result<()> {
+    writeln!(w, "[")?;
+    for (i, row) in rows.iter().enumerate() {
+        write!(w, "    ")?;
+        serde_json::to_writer(&mut w, row)?;
+        writeln!(w, "{}", if i + 1 < rows.len() { "," } else { "" })?;
+    }
+    writeln!(w, "]")?;
+
+    Ok(())
+}
+
+/// Writes just the `x` and performance of each row, with none of the other columns.
+pub fn write_call_tuples(rows: &[CallRow], mut w: impl Write) -> std::io::Result<()> {
+    writeln!(w, "[")?;
+    for CallRow { x, performance, .. } in rows {
+        writeln!(w, "    ({x}, {performance}),")?;
+    }
+    writeln!(w, "]")?;
+
+    Ok(())
+}
+
+pub fn write_calls(format: OutputFormat, rows: &[CallRow], mut w: impl Write) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Ron => {
+            writeln!(w, "[")?;
+            for CallRow { target, x, seed, performance, starved, out, waste, spend } in rows {
+                writeln!(
+                    w,
+                    "    (target: {target:?}, x: {x}, seed: {seed:?}, performance: {performance}, starved: {starved}, out: {out}, waste: {waste}, spend: {spend}),"
+                )?;
+            }
+            writeln!(w, "]")?;
+        },
+        OutputFormat::Json => {
+            write_json_lines(rows, w)?;
+        },
+        OutputFormat::Csv => {
+            writeln!(w, "{}", CALL_ROW_COLUMNS.join(","))?;
+            for CallRow { target, x, seed, performance, starved, out, waste, spend } in rows {
+                writeln!(
+                    w,
+                    "{},{x},{},{performance},{starved},{out},{waste},{spend}",
+                    csv_field(target),
+                    csv_field(&format!("{seed:?}")),
+                )?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// The performance at every combination of values of two targets.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Grid {
+    pub x_target: String,
+    pub y_target: String,
+    pub seed: Seed,
+    pub xs: Vec<f32>,
+    pub ys: Vec<f32>,
+    /// One row per `ys` value, each with one column per `xs` value.
+    pub performances: Vec<Vec<f32>>,
+}
+
+pub fn write_grid(format: OutputFormat, grid: &Grid, mut w: impl Write) -> std::io::Result<()> {
+    let Grid { x_target, y_target, seed, xs, ys, performances } = grid;
+
+    match format {
+        OutputFormat::Ron => {
+            writeln!(w, "(")?;
+            writeln!(w, "    x_target: {x_target:?},")?;
+            writeln!(w, "    y_target: {y_target:?},")?;
+            writeln!(w, "    seed: {seed:?},")?;
+            writeln!(w, "    xs: {xs:?},")?;
+            writeln!(w, "    ys: {ys:?},")?;
+            writeln!(w, "    performances: [")?;
+            for row in performances {
+                writeln!(w, "        {row:?},")?;
+            }
+            writeln!(w, "    ],")?;
+            writeln!(w, ")")?;
+        },
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut w, grid)?;
+            writeln!(w)?;
+        },
+        OutputFormat::Csv => {
+            // The top left cell labels both axes, then the x values go along the top row,
+            // and the y values go down the first column.
+            write!(w, "{}", csv_field(&format!("{y_target} \\ {x_target}")))?;
+            for x in xs {
+                write!(w, ",{x}")?;
+            }
+            writeln!(w)?;
+
+            for (y, row) in ys.iter().zip(performances) {
+                write!(w, "{y}")?;
+                for performance in row {
+                    write!(w, ",{performance}")?;
+                }
+                writeln!(w)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// How much of one food type was on the shelf at the end of one day.
+/// Day 0 is the start of the first day.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct StockRow {
+    pub day: u32,
+    pub key: String,
+    pub grams: u32,
+    pub items: u32,
+}
+
+const STOCK_ROW_COLUMNS: [&str; 4] = ["day", "key", "grams", "items"];
+
+pub fn write_stock_series(format: OutputFormat, rows: &[StockRow], mut w: impl Write) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Ron => {
+            writeln!(w, "[")?;
+            for StockRow { day, key, grams, items } in rows {
+                writeln!(w, "    (day: {day}, key: {key:?}, grams: {grams}, items: {items}),")?;
+            }
+            writeln!(w, "]")?;
+        },
+        OutputFormat::Json => {
+            write_json_lines(rows, w)?;
+        },
+        OutputFormat::Csv => {
+            writeln!(w, "{}", STOCK_ROW_COLUMNS.join(","))?;
+            for StockRow { day, key, grams, items } in rows {
+                writeln!(w, "{day},{},{grams},{items}", csv_field(key))?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod csv_field_works {
+    use super::*;
+
+    #[test]
+    fn on_plain_and_special_fields() {
+        assert_eq!(csv_field("restock.fullness_threshold"), "restock.fullness_threshold");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
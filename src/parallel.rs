@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many threads to spread independent runs over.
+pub type WorkerCount = u16;
+
+/// How many workers to use when none is configured.
+pub fn default_worker_count() -> WorkerCount {
+    std::thread::available_parallelism()
+        .map(|n| WorkerCount::try_from(n.get()).unwrap_or(WorkerCount::MAX))
+        .unwrap_or(1)
+}
+
+/// Like `items.iter().map(f).collect()`, but spreading the calls over up to `worker_count` threads.
+/// The outputs are in the same order as the items, regardless of which thread finished first, so as
+/// long as `f` is deterministic, so is the output.
+pub fn map<A: Sync, B: Send>(
+    worker_count: WorkerCount,
+    items: &[A],
+    f: impl Fn(&A) -> B + Sync,
+) -> Vec<B> {
+    let worker_count = core::cmp::min(worker_count as usize, items.len());
+
+    if worker_count <= 1 {
+        return items.iter().map(f).collect()
+    }
+
+    // Hand out items one at a time, so that workers that get quick items don't sit idle.
+    let next_index = AtomicUsize::new(0);
+
+    let mut indexed: Vec<(usize, B)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..worker_count)
+            .map(|_| scope.spawn(|| {
+                let mut outputs = Vec::new();
+
+                loop {
+                    let i = next_index.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break
+                    };
+
+                    outputs.push((i, f(item)));
+                }
+
+                outputs
+            }))
+            .collect();
+
+        handles.into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    });
+
+    indexed.sort_by_key(|&(i, _)| i);
+
+    indexed.into_iter().map(|(_, b)| b).collect()
+}
+
+#[cfg(test)]
+mod map_works {
+    use super::*;
+
+    #[test]
+    fn keeps_the_order() {
+        let items: Vec<u32> = (0..100).collect();
+
+        for worker_count in [0, 1, 3, 8, 200] {
+            assert_eq!(
+                map(worker_count, &items, |x| x * 2),
+                items.iter().map(|x| x * 2).collect::<Vec<_>>(),
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Calling the simulation at evenly spaced values of one or two targets, and writing out each result.
+
+use crate::{basic, output, parallel, svg};
+use crate::search::TargetFunc;
+use crate::types::{self, PrintCallsSpec, Res, SearchTarget, Spec};
+
+/// Simulates once at each point of the sweep, or of the grid if there is a `y` sweep.
+pub fn run(
+    spec: &Spec,
+    extras: &types::BasicExtras,
+    PrintCallsSpec {
+        x: x_sweep,
+        y: y_sweep,
+        format,
+        chart_path,
+    }: &PrintCallsSpec,
+    output: impl std::io::Write,
+) -> Res<()> {
+    let seed = spec.seed.unwrap_or_default();
+
+    let unbounded = |sweep: &types::Sweep| SearchTarget {
+        target: sweep.target.clone(),
+        lower: None,
+        upper: None,
+        integer: false,
+        offset: sweep.offset,
+        length: sweep.length,
+    };
+
+    let xs = x_sweep.points();
+
+    match y_sweep {
+        None => {
+            let func = TargetFunc::new(
+                spec,
+                extras,
+                [unbounded(x_sweep)],
+                vec![seed],
+                types::Aggregation::Mean,
+            );
+
+            let target_name = x_sweep.target.to_string();
+
+            let rows = parallel::map(spec.worker_count, &xs, |&x| {
+                let (extras, _) = func.extras_at([x]);
+
+                func.run_outputs(extras)
+                    .into_iter()
+                    .map(|run_output| run_output.map(|basic::RunOutput { performance, terms, .. }| {
+                        output::CallRow {
+                            target: target_name.clone(),
+                            x,
+                            seed,
+                            performance: performance as f32,
+                            starved: terms.starved,
+                            out: terms.out,
+                            waste: terms.waste,
+                            spend: terms.spend,
+                        }
+                    }))
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .collect::<Result<Vec<_>, _>>()?;
+
+            match format {
+                Some(format) => output::write_calls(*format, &rows, output)?,
+                None => output::write_call_tuples(&rows, output)?,
+            }
+
+            if let Some(path) = chart_path {
+                svg::write_charts(
+                    &[svg::Chart {
+                        title: "Performance (closer to 0 is better)".to_string(),
+                        x_label: target_name.clone(),
+                        y_label: "performance".to_string(),
+                        series: vec![svg::Series {
+                            label: "performance".to_string(),
+                            points: rows.iter().map(|row| (row.x, row.performance)).collect(),
+                        }],
+                        x_markers: vec![],
+                    }],
+                    std::io::BufWriter::new(std::fs::File::create(path)?),
+                )?;
+            }
+        },
+        Some(y_sweep) => {
+            let func = TargetFunc::new(
+                spec,
+                extras,
+                [unbounded(x_sweep), unbounded(y_sweep)],
+                vec![seed],
+                types::Aggregation::Mean,
+            );
+
+            let ys = y_sweep.points();
+
+            let mut points = Vec::with_capacity(xs.len() * ys.len());
+            for &y in &ys {
+                for &x in &xs {
+                    points.push([x, y]);
+                }
+            }
+
+            let performances = parallel::map(spec.worker_count, &points, |&xy| func.call(xy));
+
+            let grid = output::Grid {
+                x_target: x_sweep.target.to_string(),
+                y_target: y_sweep.target.to_string(),
+                seed,
+                performances: performances
+                    .chunks(core::cmp::max(xs.len(), 1))
+                    .map(|row| row.to_vec())
+                    .collect(),
+                xs,
+                ys,
+            };
+
+            output::write_grid(format.unwrap_or_default(), &grid, output)?;
+
+            if let Some(path) = chart_path {
+                // One line per y value.
+                svg::write_charts(
+                    &[svg::Chart {
+                        title: "Performance (closer to 0 is better)".to_string(),
+                        x_label: grid.x_target.clone(),
+                        y_label: "performance".to_string(),
+                        series: grid.ys.iter()
+                            .zip(&grid.performances)
+                            .map(|(y, row)| svg::Series {
+                                label: format!("{}: {y}", grid.y_target),
+                                points: grid.xs.iter().copied().zip(row.iter().copied()).collect(),
+                            })
+                            .collect(),
+                        x_markers: vec![],
+                    }],
+                    std::io::BufWriter::new(std::fs::File::create(path)?),
+                )?;
+            }
+        },
+    }
+
+    Ok(())
+}
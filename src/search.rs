@@ -0,0 +1,332 @@
+//! Searching for the target values that give the best performance, averaged over several runs.
+
+use crate::{aggregate, basic, minimize, parallel, xs};
+use crate::types::{self, Mode, Res, SearchSpec, SearchTarget, Spec};
+
+/// A function that sets each of the targets to the closest allowed value to the corresponding
+/// passed in value, runs the simulation once for each of the seeds, then combines the performance
+/// of each run with the aggregation.
+/// Since the simulation is deterministic given a seed, the results are cached, keyed on the values
+/// that actually end up in the targeted fields. This means for example that calls that only differ in
+/// values that get truncated to the same integer are only simulated once.
+pub(crate) struct TargetFunc<const N: usize> {
+    spec: Spec,
+    extras: types::BasicExtras,
+    targets: [SearchTarget; N],
+    seeds: Vec<types::Seed>,
+    aggregation: types::Aggregation,
+    cache: std::sync::Mutex<std::collections::HashMap<[u32; N], f32>>,
+    hits: std::sync::atomic::AtomicU32,
+}
+
+impl <const N: usize> TargetFunc<N> {
+    pub(crate) fn new(
+        spec: &Spec,
+        extras: &types::BasicExtras,
+        targets: [SearchTarget; N],
+        seeds: Vec<types::Seed>,
+        aggregation: types::Aggregation,
+    ) -> Self {
+        Self {
+            spec: spec.without_files(),
+            extras: extras.clone(),
+            targets,
+            seeds,
+            aggregation,
+            cache: <_>::default(),
+            hits: <_>::default(),
+        }
+    }
+
+    /// Sets each of the targets to the closest allowed value to the corresponding passed in value.
+    /// Returns the resulting extras, and the values that actually ended up in the targeted fields.
+    pub(crate) fn extras_at(&self, xs: [f32; N]) -> (types::BasicExtras, [u32; N]) {
+        let mut extras = self.extras.clone();
+
+        let mut key = [0; N];
+
+        for i in 0..N {
+            let target = &self.targets[i];
+            target.target.set(&mut extras, target.project(xs[i]));
+
+            key[i] = target.target.get(&extras).unwrap_or(f32::NAN).to_bits();
+        }
+
+        (extras, key)
+    }
+
+    /// Runs the simulation once for each of the seeds, without caching.
+    pub(crate) fn run_outputs(&self, extras: types::BasicExtras) -> Vec<Result<basic::RunOutput, std::io::Error>> {
+        let run_spec = Spec {
+            mode: Mode::Basic(types::BasicExtras {
+                mode: types::BasicMode::Run,
+                ..extras
+            }),
+            ..self.spec.clone()
+        };
+
+        parallel::map(self.spec.worker_count, &self.seeds, |&seed| {
+            basic::run(
+                &Spec {
+                    seed: Some(seed),
+                    ..run_spec.clone()
+                },
+                std::io::sink(),
+            )
+        })
+    }
+
+    pub(crate) fn call(&self, xs: [f32; N]) -> f32 {
+        let (extras, key) = self.extras_at(xs);
+
+        if let Some(&y) = self.cache().get(&key) {
+            self.hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            return y
+        }
+
+        let performances: Vec<f32> = self.run_outputs(extras)
+            .into_iter()
+            .map(|run_output| {
+                run_output.map(|o| o.performance)
+                    .unwrap_or(basic::Performance::MAX) as f32
+            })
+            .collect();
+
+        let y = aggregate::aggregate(self.aggregation, &performances);
+
+        self.cache().insert(key, y);
+
+        y
+    }
+
+    fn cache(&self) -> std::sync::MutexGuard<'_, std::collections::HashMap<[u32; N], f32>> {
+        // A panic while holding the lock cannot leave the map in an inconsistent state.
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn hits(&self) -> u32 {
+        self.hits.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn misses(&self) -> u32 {
+        self.cache().len() as u32
+    }
+}
+
+/// Searches with the optimizer in the spec, then writes out what it found.
+pub fn run(
+    spec: &Spec,
+    extras: &types::BasicExtras,
+    search_spec: &SearchSpec,
+    output: impl std::io::Write,
+) -> Res<()> {
+    macro_rules! search_n {
+        ($($n: literal)+) => {
+            match search_spec.targets.len() {
+                $( $n => search::<$n>(spec, extras, search_spec, output), )+
+                _ => unreachable!("should have been caught in config validation"),
+            }
+        }
+    }
+
+    // Should be kept in sync with `types::MAX_SEARCH_TARGETS`.
+    search_n!(1 2 3 4)
+}
+
+fn search<const N: usize>(
+    spec: &Spec,
+    extras: &types::BasicExtras,
+    SearchSpec {
+        targets,
+        sample_count,
+        aggregation,
+        optimizer,
+        max_iterations,
+        x_tolerance,
+        y_tolerance,
+        show_trace,
+    }: &SearchSpec,
+    mut output: impl std::io::Write,
+) -> Res<()> {
+    use minimize::{Call, Iteration, Minimized, Region, simplex_centered_at};
+    use types::Optimizer;
+
+    let targets: [SearchTarget; N] = targets.to_vec().try_into()
+        .map_err(|_| format!("Expected {N} targets, got {}", targets.len()))?;
+
+    // Use the same seeds for every evaluation, so that differences in performance
+    // are due to the target values, and not the random history.
+    let seeds = xs::chained_seeds(spec.seed.unwrap_or_default(), *sample_count as usize);
+
+    let func = TargetFunc::new(spec, extras, targets.clone(), seeds, *aggregation);
+
+    let center = targets.each_ref().map(|search_target| search_target.offset + search_target.length);
+
+    let simplex = simplex_centered_at(targets.each_ref().map(|search_target| search_target.length), center);
+
+    let region = Region {
+        lower: targets.each_ref().map(|search_target| {
+            search_target.lower.unwrap_or(search_target.offset)
+        }),
+        upper: targets.each_ref().map(|search_target| {
+            search_target.upper.unwrap_or(search_target.offset + 2. * search_target.length)
+        }),
+    };
+
+    let options = minimize::Options {
+        max_iterations: *max_iterations,
+        x_tolerance: *x_tolerance,
+        y_tolerance: *y_tolerance,
+    };
+
+    let mut rng = xs::from_seed(spec.seed.unwrap_or_default());
+
+    writeln!(output, "optimizer: {optimizer:?},")?;
+    match optimizer {
+        Optimizer::NelderMead | Optimizer::RandomRestart { .. } => {
+            writeln!(output, "simplex: {simplex:#?},")?;
+        },
+        Optimizer::Grid { .. } | Optimizer::Evolution { .. } => {
+            writeln!(output, "region: {region:?},")?;
+        },
+    }
+
+    if *show_trace {
+        writeln!(output, "trace: [")?;
+    }
+
+    let mut trace_result = Ok(());
+
+    let on_iteration = |Iteration { index, best, diameter }: Iteration<N>| {
+        if *show_trace && trace_result.is_ok() {
+            trace_result = writeln!(
+                output,
+                "    (iteration: {index}, xs: {:?}, y: {}, diameter: {diameter}),",
+                best.xs,
+                best.y,
+            );
+        }
+    };
+
+    let f = |xs| func.call(xs);
+
+    let Minimized {
+        call: Call { xs, y: performance },
+        iterations,
+        evaluations,
+        converged,
+    } = match *optimizer {
+        Optimizer::NelderMead => minimize::minimize(f, simplex, options, on_iteration),
+        Optimizer::Grid { steps } => minimize::grid_search(f, region, steps, on_iteration),
+        Optimizer::RandomRestart { restarts } => minimize::random_restarts(
+            f,
+            simplex,
+            region,
+            restarts,
+            options,
+            &mut rng,
+            on_iteration,
+        ),
+        Optimizer::Evolution { population } => minimize::evolve(
+            f,
+            region,
+            population,
+            options,
+            &mut rng,
+            on_iteration,
+        ),
+    };
+
+    trace_result?;
+
+    if *show_trace {
+        writeln!(output, "],")?;
+    }
+
+    writeln!(output, "iterations: {iterations},")?;
+    writeln!(output, "evaluations: {evaluations},")?;
+    writeln!(output, "converged: {converged},")?;
+    let hits = func.hits();
+    let lookups = hits + func.misses();
+    writeln!(
+        output,
+        "cache_hit_rate: {}, // {hits} hits out of {lookups} lookups",
+        if lookups == 0 { 0. } else { hits as f32 / lookups as f32 },
+    )?;
+    writeln!(output, "sample_count: {sample_count},")?;
+    writeln!(output, "aggregation: {aggregation:?},")?;
+    for i in 0..N {
+        let x = targets[i].project(xs[i]);
+
+        match targets[i].bound_at(x) {
+            Some(bound) => writeln!(output, "{}: {x}, // On the {bound:?} bound", targets[i].target)?,
+            None => writeln!(output, "{}: {x},", targets[i].target)?,
+        }
+    }
+    writeln!(output, "performance (closer to 0 is better): {performance},")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod target_func_works {
+    use super::*;
+    use crate::event_source::{BuyIfHalfEmptyParams, BuyNOfEverythingParams, EventSource, FixedHungerAmountParams};
+    use crate::types::{food, EventSourceSpec, Target, TargetSpecs};
+    use vec1::vec1;
+
+    #[test]
+    fn on_values_that_round_to_the_same_integer() {
+        fn es_spec(source: impl EventSource + 'static) -> EventSourceSpec {
+            EventSourceSpec {
+                source: Box::new(source),
+                recurrence: vec![],
+                label: String::new(),
+            }
+        }
+
+        let extras = types::BasicExtras {
+            mode: types::BasicMode::Run,
+            food_types: vec1![food::Type::new(
+                "Bread",
+                food::Option { grams: 600, price: 100, ..food::Option::default() },
+            )],
+            initial_event_source_specs: vec1![es_spec(BuyNOfEverythingParams { n: 1 })],
+            repeated_event_source_specs: vec1![
+                es_spec(FixedHungerAmountParams { grams_per_day: 300, ..FixedHungerAmountParams::default() }),
+                es_spec(BuyIfHalfEmptyParams { max_count: 1, offset: 0 }),
+            ],
+            objective: types::Objective::default(),
+            recipes: Vec::new(),
+            substitution_policy: types::SubstitutionPolicy::default(),
+        };
+        let spec = Spec {
+            mode: Mode::Basic(extras.clone()),
+            seed: Some([7; 16]),
+            day_count_min: 5,
+            day_count_one_past_max: 6,
+            hide_summary: true,
+            ..Spec::default()
+        };
+        let target = SearchTarget {
+            target: Target {
+                specs: TargetSpecs::Name("BuyIfHalfEmpty".to_string()),
+                field: "max_count".to_string(),
+            },
+            lower: None,
+            upper: None,
+            integer: true,
+            offset: 0.,
+            length: 2.,
+        };
+
+        let func = TargetFunc::new(&spec, &extras, [target], vec![[7; 16]], types::Aggregation::Mean);
+
+        let y = func.call([1.2]);
+        assert_eq!(func.call([1.4]), y);
+        assert_eq!((func.hits(), func.misses()), (1, 1));
+
+        func.call([2.]);
+        assert_eq!((func.hits(), func.misses()), (1, 2));
+    }
+}
@@ -0,0 +1,214 @@
+//! Minimal line charts, written out as SVG.
+
+use std::io::Write;
+
+/// A line to draw on a chart.
+#[derive(Clone, Debug, Default)]
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f32, f32)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Chart {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub series: Vec<Series>,
+    /// X values to mark with a vertical line, for example day boundaries.
+    pub x_markers: Vec<f32>,
+}
+
+const WIDTH: f32 = 800.;
+const CHART_HEIGHT: f32 = 300.;
+const MARGIN_LEFT: f32 = 80.;
+const MARGIN_RIGHT: f32 = 160.;
+const MARGIN_TOP: f32 = 40.;
+const MARGIN_BOTTOM: f32 = 50.;
+const TICK_COUNT: u8 = 5;
+
+const COLOURS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+];
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn tick_label(value: f32) -> String {
+    if value.fract() == 0. || value.abs() >= 100. {
+        format!("{value:.0}")
+    } else {
+        let label = format!("{value:.3}");
+        label.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// The smallest and largest values, widened if needed so that the range is never empty.
+fn extent(values: impl Iterator<Item = f32>) -> (f32, f32) {
+    let (min, max) = values.fold(
+        (f32::INFINITY, f32::NEG_INFINITY),
+        |(min, max), v| (min.min(v), max.max(v)),
+    );
+
+    if min > max {
+        (0., 1.)
+    } else if min == max {
+        (min - 0.5, max + 0.5)
+    } else {
+        (min, max)
+    }
+}
+
+/// Writes the charts stacked vertically, each with its own axes, into one SVG document.
+pub fn write_charts(charts: &[Chart], mut w: impl Write) -> std::io::Result<()> {
+    let height = CHART_HEIGHT * charts.len() as f32;
+
+    writeln!(
+        w,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="sans-serif" font-size="12">"#
+    )?;
+    writeln!(w, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+
+    for (chart_index, chart) in charts.iter().enumerate() {
+        let top = CHART_HEIGHT * chart_index as f32;
+
+        let left = MARGIN_LEFT;
+        let right = WIDTH - MARGIN_RIGHT;
+        let plot_top = top + MARGIN_TOP;
+        let plot_bottom = top + CHART_HEIGHT - MARGIN_BOTTOM;
+
+        let all_points = || chart.series.iter().flat_map(|series| series.points.iter());
+
+        let (x_min, x_max) = extent(all_points().map(|&(x, _)| x));
+        let (y_min, y_max) = extent(all_points().map(|&(_, y)| y));
+
+        let to_x = |x: f32| left + (x - x_min) / (x_max - x_min) * (right - left);
+        let to_y = |y: f32| plot_bottom - (y - y_min) / (y_max - y_min) * (plot_bottom - plot_top);
+
+        writeln!(
+            w,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-size="14">{}</text>"#,
+            (left + right) / 2.,
+            top + MARGIN_TOP / 2.,
+            escape(&chart.title),
+        )?;
+
+        for &marker in &chart.x_markers {
+            let x = to_x(marker);
+            writeln!(
+                w,
+                r#"<line x1="{x}" y1="{plot_top}" x2="{x}" y2="{plot_bottom}" stroke="lightgrey" stroke-dasharray="4 4"/>"#
+            )?;
+        }
+
+        for i in 0..=TICK_COUNT {
+            let t = i as f32 / TICK_COUNT as f32;
+
+            let y_value = y_min + (y_max - y_min) * t;
+            let y = to_y(y_value);
+            writeln!(w, r#"<line x1="{}" y1="{y}" x2="{left}" y2="{y}" stroke="black"/>"#, left - 5.)?;
+            writeln!(
+                w,
+                r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+                left - 8.,
+                y + 4.,
+                tick_label(y_value),
+            )?;
+
+            let x_value = x_min + (x_max - x_min) * t;
+            let x = to_x(x_value);
+            writeln!(w, r#"<line x1="{x}" y1="{plot_bottom}" x2="{x}" y2="{}" stroke="black"/>"#, plot_bottom + 5.)?;
+            writeln!(
+                w,
+                r#"<text x="{x}" y="{}" text-anchor="middle">{}</text>"#,
+                plot_bottom + 18.,
+                tick_label(x_value),
+            )?;
+        }
+
+        writeln!(
+            w,
+            r#"<polyline points="{left},{plot_top} {left},{plot_bottom} {right},{plot_bottom}" fill="none" stroke="black"/>"#
+        )?;
+
+        writeln!(
+            w,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            (left + right) / 2.,
+            plot_bottom + 38.,
+            escape(&chart.x_label),
+        )?;
+        writeln!(
+            w,
+            r#"<text x="{}" y="{}" text-anchor="middle" transform="rotate(-90 {} {})">{}</text>"#,
+            left - 60.,
+            (plot_top + plot_bottom) / 2.,
+            left - 60.,
+            (plot_top + plot_bottom) / 2.,
+            escape(&chart.y_label),
+        )?;
+
+        for (series_index, series) in chart.series.iter().enumerate() {
+            let colour = COLOURS[series_index % COLOURS.len()];
+
+            write!(w, r#"<polyline fill="none" stroke="{colour}" stroke-width="1.5" points=""#)?;
+            for &(x, y) in &series.points {
+                write!(w, "{},{} ", to_x(x), to_y(y))?;
+            }
+            writeln!(w, r#""/>"#)?;
+
+            let legend_y = plot_top + 16. * series_index as f32;
+            writeln!(
+                w,
+                r#"<line x1="{}" y1="{legend_y}" x2="{}" y2="{legend_y}" stroke="{colour}" stroke-width="2"/>"#,
+                right + 10.,
+                right + 30.,
+            )?;
+            writeln!(
+                w,
+                r#"<text x="{}" y="{}">{}</text>"#,
+                right + 35.,
+                legend_y + 4.,
+                escape(&series.label),
+            )?;
+        }
+    }
+
+    writeln!(w, "</svg>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod write_charts_works {
+    use super::*;
+
+    #[test]
+    fn on_one_series() {
+        let mut output = Vec::new();
+
+        write_charts(
+            &[Chart {
+                title: "Grams & Items".to_string(),
+                series: vec![Series {
+                    label: "<total>".to_string(),
+                    points: vec![(0., 0.), (1., 2.)],
+                }],
+                ..Chart::default()
+            }],
+            &mut output,
+        ).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("<svg"));
+        assert!(output.trim_end().ends_with("</svg>"));
+        assert!(output.contains("Grams &amp; Items"));
+        assert!(output.contains("&lt;total&gt;"));
+        assert!(!output.contains("NaN"));
+    }
+}
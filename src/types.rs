@@ -14,51 +14,147 @@ pub type IndexOffset = usize;
 
 pub type FullnessThreshold = f32;
 
-#[derive(Clone, Debug)]
-pub struct BuyAllBasedOnFullnessParams {
-    pub max_count: ShoppingCount,
-    pub offset: IndexOffset,
-    pub fullness_threshold: FullnessThreshold,
-    pub minimum_purchase_servings: food::Servings,
+/// In the smallest unit of currency, for example cents.
+/// Also used for the total spent over a whole run, which is why this is wider than `Grams`.
+pub type Money = u32;
+
+/// How much each of the measured quantities counts towards the overall performance.
+/// Each term is multiplied by its weight, and the results are summed.
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct Objective {
+    /// Per time we had nothing at all to eat.
+    pub starved_weight: f32,
+    /// Per gram we wanted to eat of something but had run out of.
+    pub out_weight: f32,
+    /// Per gram of food that spoiled.
+    pub waste_weight: f32,
+    /// Per unit of money spent.
+    pub spend_weight: f32,
 }
 
-#[derive(Clone, Debug)]
-pub struct BuyIfHalfEmptyParams {
-    pub max_count: ShoppingCount,
-    pub offset: IndexOffset,
+impl Default for Objective {
+    fn default() -> Self {
+        Self {
+            starved_weight: 1000.,
+            out_weight: 1.,
+            waste_weight: 1.,
+            spend_weight: 1.,
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct BuyRandomVarietyParams {
-    pub count: ShoppingCount,
-    pub offset: IndexOffset,
+/// A field of a params struct, that can be targeted by a `Target`, if it is numeric.
+pub trait Field {
+    /// Whether the field only holds whole numbers.
+    const INTEGER: bool;
+
+    fn get_f32(&self) -> Option<f32>;
+    /// Returns `None` if the field is not numeric.
+    fn set_f32(&mut self, x: f32) -> Option<()>;
 }
 
-#[derive(Clone, Debug)]
-pub struct BuyNOfEverythingParams {
-    pub n: ShoppingCount,
+macro_rules! numeric_field_def {
+    ($($type: ty : $integer: literal)+) => {
+        $(
+            impl Field for $type {
+                const INTEGER: bool = $integer;
+
+                fn get_f32(&self) -> Option<f32> {
+                    Some(*self as f32)
+                }
+
+                fn set_f32(&mut self, x: f32) -> Option<()> {
+                    *self = x as _;
+                    Some(())
+                }
+            }
+        )+
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct BuyExactlyParams {
-    pub key_to_buy: food::Key,
-    pub grams_to_buy: food::Grams,
+numeric_field_def!{ u8: true u16: true usize: true f32: false }
+
+impl Field for String {
+    const INTEGER: bool = false;
+
+    fn get_f32(&self) -> Option<f32> {
+        None
+    }
+
+    fn set_f32(&mut self, _: f32) -> Option<()> {
+        None
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct EatExactlyParams {
-    pub key_to_eat: food::Key,
-    pub grams_to_eat: food::Grams,
+impl <A> Field for Vec<A> {
+    const INTEGER: bool = false;
+
+    fn get_f32(&self) -> Option<f32> {
+        None
+    }
+
+    fn set_f32(&mut self, _: f32) -> Option<()> {
+        None
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct FixedHungerAmountParams {
-    pub grams_per_day: food::Grams,
+/// Access to the fields of a params struct by name.
+pub trait Params {
+    fn field_names(&self) -> &'static [&'static str];
+
+    /// Returns `None` if there is no numeric field with the given name.
+    fn get(&self, field: &str) -> Option<f32>;
+
+    /// Returns `None` if there is no numeric field with the given name.
+    fn set(&mut self, field: &str, x: f32) -> Option<()>;
+
+    /// Returns `None` if there is no field with the given name.
+    fn is_integer(&self, field: &str) -> Option<bool>;
 }
 
-#[derive(Clone, Debug)]
-pub struct FixedServingsAmountParams {
-    pub servings_per_day: food::Servings,
+/// Defines a params struct, with public fields that can be accessed by name through `Params`.
+/// The struct can be deserialized, with any missing fields getting their default values.
+#[macro_export]
+macro_rules! params_def {
+    (
+        pub struct $name: ident {
+            $($(#[$field_meta: meta])* pub $field: ident : $type: ty,)+
+        }
+    ) => {
+        #[derive(Clone, Debug, Default, serde::Deserialize)]
+        #[serde(default)]
+        pub struct $name {
+            $($(#[$field_meta])* pub $field: $type,)+
+        }
+
+        impl $crate::types::Params for $name {
+            fn field_names(&self) -> &'static [&'static str] {
+                &[$(stringify!($field),)+]
+            }
+
+            fn get(&self, field: &str) -> Option<f32> {
+                match field {
+                    $( stringify!($field) => $crate::types::Field::get_f32(&self.$field), )+
+                    _ => None,
+                }
+            }
+
+            fn set(&mut self, field: &str, x: f32) -> Option<()> {
+                match field {
+                    $( stringify!($field) => $crate::types::Field::set_f32(&mut self.$field, x), )+
+                    _ => None,
+                }
+            }
+
+            fn is_integer(&self, field: &str) -> Option<bool> {
+                match field {
+                    $( stringify!($field) => Some(<$type as $crate::types::Field>::INTEGER), )+
+                    _ => None,
+                }
+            }
+        }
+    }
 }
 
 /// One past max value of a die to roll from 0 to. So a value of 6 indicates a roll between 6 values from
@@ -79,74 +175,544 @@ impl RollOnePastMax {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct ShopSomeDaysParams {
-    pub buy_count: u8,
-    pub roll_one_past_max: RollOnePastMax,
+impl Field for RollOnePastMax {
+    const INTEGER: bool = true;
+
+    fn get_f32(&self) -> Option<f32> {
+        Some(self.0.get() as f32)
+    }
+
+    fn set_f32(&mut self, x: f32) -> Option<()> {
+        self.0 = NonZeroU8::new(x as u8).unwrap_or(NonZeroU8::MIN);
+        Some(())
+    }
 }
 
+pub type Recurrence = Vec<u8>;
+
 #[derive(Clone, Debug)]
-pub struct RandomEventParams {
-    pub roll_one_past_max: RollOnePastMax,
+pub struct EventSourceSpec {
+    pub source: Box<dyn crate::event_source::EventSource>,
+    pub recurrence: Recurrence,
+    /// An optional name, so targets can refer to this spec.
+    pub label: String,
 }
 
-macro_rules! essk_def {
-    (
-        $($variant: ident ($params: ident) $(,)? )+
-    ) => {
-        #[derive(Clone, Debug)]
-        pub enum EventSourceSpecKind {
-            $( $variant($params), )+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpecList {
+    Initial,
+    Repeated,
+}
+
+impl SpecList {
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Initial => "initial_event_source_specs",
+            Self::Repeated => "repeated_event_source_specs",
+        }
+    }
+}
+
+/// Which event source specs a `Target` applies to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TargetSpecs {
+    /// A single spec, by its position in one of the lists.
+    Index(SpecList, usize),
+    /// Every spec with the given label, or if none have that label, every spec of the kind with that name.
+    Name(String),
+    /// Like `Name`, but only the specs in the given list.
+    ListName(SpecList, String),
+}
+
+/// A numeric field of one or more event source specs, that can be set to different values, say to
+/// search for the best value. Written as either `repeated_event_source_specs[1].fullness_threshold`,
+/// or `label.fullness_threshold` where `label` is either the label of a spec, or the name of a kind
+/// of spec, like `BuyIfBelowThreshold`. The latter can be limited to one of the lists, as in
+/// `repeated_event_source_specs.label.fullness_threshold`.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct Target {
+    pub specs: TargetSpecs,
+    pub field: String,
+}
+
+impl Default for Target {
+    /// Only the repeated specs, as it was before targets were paths.
+    fn default() -> Self {
+        Self {
+            specs: TargetSpecs::ListName(SpecList::Repeated, "BuyIfBelowThreshold".to_string()),
+            field: "fullness_threshold".to_string(),
         }
+    }
+}
 
-        #[derive(Debug, serde::Deserialize)]
-        pub enum RawEventSourceSpecKind {
-            $( $variant, )+
+impl TryFrom<String> for Target {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        // Names from before targets were paths.
+        match s.as_str() {
+            "BuyIfBelowThresholdFullnessThreshold" => return Ok(Self::default()),
+            "BuyIfBelowThresholdMinimumPurchaseServings" => return Ok(Self {
+                field: "minimum_purchase_servings".to_string(),
+                ..Self::default()
+            }),
+            _ => {}
         }
+
+        let Some((specs_part, field)) = s.rsplit_once('.') else {
+            return Err(format!("Expected a \".\" followed by a field name in target \"{s}\""));
+        };
+
+        let find_list = |list_key: &str| {
+            [SpecList::Initial, SpecList::Repeated].into_iter()
+                .find(|list| list.key() == list_key)
+                .ok_or_else(|| format!("Unknown list \"{list_key}\" in target \"{s}\""))
+        };
+
+        let specs = match specs_part.split_once('[') {
+            Some((list_key, rest)) => {
+                let list = find_list(list_key)?;
+
+                let index = rest.strip_suffix(']')
+                    .and_then(|index| index.parse().ok())
+                    .ok_or_else(|| format!("Expected an index like [0] in target \"{s}\""))?;
+
+                TargetSpecs::Index(list, index)
+            },
+            None => match specs_part.split_once('.') {
+                Some((list_key, name)) => TargetSpecs::ListName(find_list(list_key)?, name.to_string()),
+                None => TargetSpecs::Name(specs_part.to_string()),
+            },
+        };
+
+        Ok(Self {
+            specs,
+            field: field.to_string(),
+        })
     }
 }
 
-essk_def!{
-    BuyIfBelowThreshold(BuyAllBasedOnFullnessParams),
-    BuyIfHalfEmpty(BuyIfHalfEmptyParams),
-    BuyRandomVariety(BuyRandomVarietyParams),
-    BuyNOfEverything(BuyNOfEverythingParams),
-    BuyExactly(BuyExactlyParams),
-    EatExactly(EatExactlyParams),
-    FixedHungerAmount(FixedHungerAmountParams),
-    FixedServingsAmount(FixedServingsAmountParams),
-    ShopSomeDays(ShopSomeDaysParams),
-    RandomEvent(RandomEventParams),
+impl core::fmt::Display for Target {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.specs {
+            TargetSpecs::Index(list, index) => write!(f, "{}[{index}].{}", list.key(), self.field),
+            TargetSpecs::Name(name) => write!(f, "{name}.{}", self.field),
+            TargetSpecs::ListName(list, name) => write!(f, "{}.{name}.{}", list.key(), self.field),
+        }
+    }
 }
 
-pub type Recurrence = Vec<u8>;
+impl Target {
+    /// The list and index of each spec this target applies to.
+    fn matching_indices(
+        &self,
+        initial: &[EventSourceSpec],
+        repeated: &[EventSourceSpec],
+    ) -> Vec<(SpecList, usize)> {
+        let lists = [
+            (SpecList::Initial, initial),
+            (SpecList::Repeated, repeated),
+        ];
+
+        let mut output = Vec::new();
+
+        match &self.specs {
+            TargetSpecs::Index(list, index) => {
+                for (l, specs) in lists {
+                    if l == *list && *index < specs.len() {
+                        output.push((l, *index));
+                    }
+                }
+            },
+            TargetSpecs::Name(name) | TargetSpecs::ListName(_, name) => {
+                let lists: Vec<_> = lists.into_iter()
+                    .filter(|(l, _)| match &self.specs {
+                        TargetSpecs::ListName(list, _) => l == list,
+                        _ => true,
+                    })
+                    .collect();
+
+                let any_labelled = lists.iter()
+                    .any(|(_, specs)| specs.iter().any(|ess| &ess.label == name));
+
+                for (l, specs) in lists {
+                    for (i, ess) in specs.iter().enumerate() {
+                        let matches = if any_labelled {
+                            &ess.label == name
+                        } else {
+                            ess.source.name() == name
+                        };
+
+                        if matches {
+                            output.push((l, i));
+                        }
+                    }
+                }
+            },
+        }
+
+        output
+    }
+
+    /// The value of the targeted field of the first matching spec.
+    pub fn get(&self, extras: &BasicExtras) -> Option<f32> {
+        let (list, i) = *self.matching_indices(
+            &extras.initial_event_source_specs,
+            &extras.repeated_event_source_specs,
+        ).first()?;
+
+        let specs = match list {
+            SpecList::Initial => &extras.initial_event_source_specs,
+            SpecList::Repeated => &extras.repeated_event_source_specs,
+        };
+
+        specs[i].source.params().get(&self.field)
+    }
+
+    /// Set the targeted field of every matching spec to `x`.
+    pub fn set(&self, extras: &mut BasicExtras, x: f32) {
+        let indices = self.matching_indices(
+            &extras.initial_event_source_specs,
+            &extras.repeated_event_source_specs,
+        );
+
+        for (list, i) in indices {
+            let specs = match list {
+                SpecList::Initial => &mut extras.initial_event_source_specs,
+                SpecList::Repeated => &mut extras.repeated_event_source_specs,
+            };
+
+            // Targets are checked when the config is loaded, so this should always succeed.
+            let _ = specs[i].source.params_mut().set(&self.field, x);
+        }
+    }
+
+    /// Returns an error message if the target does not refer to a numeric field of at least one spec.
+    pub fn validate(
+        &self,
+        initial: &[EventSourceSpec],
+        repeated: &[EventSourceSpec],
+    ) -> Result<(), String> {
+        let indices = self.matching_indices(initial, repeated);
+
+        if indices.is_empty() {
+            return Err(format!("No event source specs match target \"{self}\""));
+        }
+
+        for (list, i) in indices {
+            let source = match list {
+                SpecList::Initial => &initial[i].source,
+                SpecList::Repeated => &repeated[i].source,
+            };
+
+            let params = source.params();
+            if params.get(&self.field).is_none() {
+                return Err(format!(
+                    "Target \"{self}\" refers to a {} spec, which has no numeric field \"{}\". Fields: {:?}",
+                    source.name(),
+                    self.field,
+                    params.field_names(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the first matching field only holds whole numbers.
+    pub fn is_integer(
+        &self,
+        initial: &[EventSourceSpec],
+        repeated: &[EventSourceSpec],
+    ) -> bool {
+        self.matching_indices(initial, repeated)
+            .first()
+            .and_then(|&(list, i)| {
+                let source = match list {
+                    SpecList::Initial => &initial[i].source,
+                    SpecList::Repeated => &repeated[i].source,
+                };
+
+                source.params().is_integer(&self.field)
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod target_parsing_works {
+    use super::*;
+
+    #[test]
+    fn on_paths() {
+        assert_eq!(
+            Target::try_from("repeated_event_source_specs[1].fullness_threshold".to_string()),
+            Ok(Target {
+                specs: TargetSpecs::Index(SpecList::Repeated, 1),
+                field: "fullness_threshold".to_string(),
+            }),
+        );
+
+        assert_eq!(
+            Target::try_from("restock.max_count".to_string()),
+            Ok(Target {
+                specs: TargetSpecs::Name("restock".to_string()),
+                field: "max_count".to_string(),
+            }),
+        );
+
+        assert_eq!(
+            Target::try_from("repeated_event_source_specs.BuyIfBelowThreshold.fullness_threshold".to_string()),
+            Ok(Target::default()),
+        );
+    }
+
+    #[test]
+    fn on_old_names() {
+        assert_eq!(
+            Target::try_from("BuyIfBelowThresholdFullnessThreshold".to_string()),
+            Ok(Target::default()),
+        );
+    }
+
+    #[test]
+    fn on_bad_paths() {
+        assert!(Target::try_from("fullness_threshold".to_string()).is_err());
+        assert!(Target::try_from("some_other_list[1].n".to_string()).is_err());
+        assert!(Target::try_from("initial_event_source_specs[one].n".to_string()).is_err());
+        assert!(Target::try_from("some_other_list.restock.n".to_string()).is_err());
+    }
+}
+
+/// A target to search over, and the values the search is allowed to try.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchTarget {
+    pub target: Target,
+    pub lower: Option<f32>,
+    pub upper: Option<f32>,
+    /// Whether to only try whole numbers.
+    pub integer: bool,
+    /// The search starts around `offset + length`, spread out by `length`, and unless there are
+    /// bounds, is limited to between `offset` and `offset + 2 * length` where that matters.
+    pub offset: f32,
+    pub length: f32,
+}
+
+/// Which bound, if any, a value sits on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Lower,
+    Upper,
+}
+
+impl SearchTarget {
+    /// The closest value to `x` that is allowed.
+    pub fn project(&self, mut x: f32) -> f32 {
+        if let Some(lower) = self.lower {
+            x = x.max(lower);
+        }
+        if let Some(upper) = self.upper {
+            x = x.min(upper);
+        }
+
+        if self.integer {
+            x = x.round();
+
+            // Rounding may have taken us out of bounds again.
+            if let Some(lower) = self.lower && x < lower {
+                x = lower.ceil();
+            }
+            if let Some(upper) = self.upper && x > upper {
+                x = upper.floor();
+            }
+        }
+
+        x
+    }
+
+    /// Which bound, if any, the projection of `x` sits on.
+    pub fn bound_at(&self, x: f32) -> Option<Bound> {
+        let x = self.project(x);
+
+        if self.lower.is_some_and(|lower| self.project(lower) == x) {
+            Some(Bound::Lower)
+        } else if self.upper.is_some_and(|upper| self.project(upper) == x) {
+            Some(Bound::Upper)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod project_works {
+    use super::*;
+
+    #[test]
+    fn on_integer_bounds() {
+        let search_target = SearchTarget {
+            target: Target::default(),
+            lower: Some(0.5),
+            upper: Some(3.5),
+            integer: true,
+            offset: 0.,
+            length: 1.,
+        };
+
+        assert_eq!(search_target.project(-1.), 1.);
+        assert_eq!(search_target.project(2.4), 2.);
+        assert_eq!(search_target.project(10.), 3.);
+
+        assert_eq!(search_target.bound_at(-1.), Some(Bound::Lower));
+        assert_eq!(search_target.bound_at(2.4), None);
+        assert_eq!(search_target.bound_at(3.4), Some(Bound::Upper));
+    }
+}
+
+/// The most targets that can be searched over at once.
+pub const MAX_SEARCH_TARGETS: usize = 4;
+
+/// Each sample is a whole simulation run, so any more than fits in a u16 would take far too long.
+pub type SampleCount = u16;
+
+/// How to combine the performance of several runs into a single value.
+#[derive(Clone, Copy, Debug)]
+pub enum Aggregation {
+    Mean,
+    WorstCase,
+    /// Between 0 and 100 inclusive.
+    Percentile(f32),
+}
+
+/// Which method to use to look for the best target values.
+#[derive(Clone, Copy, Debug)]
+pub enum Optimizer {
+    NelderMead,
+    /// Try every point on a grid with this many points along each target's axis.
+    Grid { steps: u16 },
+    /// Nelder–Mead from the usual starting point, then again from this many random ones.
+    RandomRestart { restarts: u16 },
+    /// A simple evolution strategy with this many candidates per generation.
+    Evolution { population: u16 },
+}
 
 #[derive(Clone, Debug)]
-pub struct EventSourceSpec {
-    pub kind: EventSourceSpecKind,
-    pub recurrence: Recurrence,
+pub struct SearchSpec {
+    pub targets: Vec1<SearchTarget>,
+    pub sample_count: SampleCount,
+    pub aggregation: Aggregation,
+    pub optimizer: Optimizer,
+    /// Passed along as `minimize::Options::max_iterations`.
+    pub max_iterations: u16,
+    pub x_tolerance: f32,
+    pub y_tolerance: f32,
+    pub show_trace: bool,
 }
 
+/// How to write out tabular results, like the calls in PrintCalls mode.
 #[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
-pub enum Target {
+pub enum OutputFormat {
     #[default]
-    BuyIfBelowThresholdFullnessThreshold,
-    BuyIfBelowThresholdMinimumPurchaseServings,
+    Ron,
+    Json,
+    Csv,
 }
 
+/// The values from `offset` to `offset + length` inclusive, `step` apart, to try for a target.
 #[derive(Clone, Debug)]
-pub struct SearchSpec {
+pub struct Sweep {
     pub target: Target,
     pub length: f32,
     pub offset: f32,
+    pub step: f32,
+}
+
+impl Sweep {
+    /// The most calls PrintCalls will make, whether along one sweep, or over a grid of two. Grid
+    /// searches are held to this too. It fits in a `u16`, so iteration counts can't saturate.
+    pub const MAX_POINTS: usize = u16::MAX as usize;
+
+    /// About how many values `points` will return, or `usize::MAX` if the fields are not finite.
+    /// Assumes `step` is above 0.
+    pub fn point_count(&self) -> usize {
+        let count = (self.length as f64 / self.step as f64).floor() + 1.;
+
+        if count.is_finite() && self.offset.is_finite() {
+            count.max(0.) as usize
+        } else {
+            usize::MAX
+        }
+    }
+
+    /// Stops early if adding the step to a large value rounds back to the same value, and never
+    /// returns more than `MAX_POINTS` values.
+    pub fn points(&self) -> Vec<f32> {
+        let mut output = Vec::new();
+
+        let mut x = self.offset;
+        let end = self.offset + self.length;
+        while x <= end && output.len() < Self::MAX_POINTS {
+            output.push(x);
+
+            let next = x + self.step;
+            if next <= x {
+                break
+            }
+            x = next;
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod sweep_works {
+    use super::*;
+
+    fn sweep(offset: f32, length: f32, step: f32) -> Sweep {
+        Sweep {
+            target: Target::default(),
+            length,
+            offset,
+            step,
+        }
+    }
+
+    #[test]
+    fn on_a_few_steps() {
+        assert_eq!(sweep(1., 1., 0.25).points(), vec![1., 1.25, 1.5, 1.75, 2.]);
+        assert_eq!(sweep(1., 1., 0.25).point_count(), 5);
+    }
+
+    #[test]
+    fn on_steps_that_do_not_move() {
+        assert_eq!(sweep(1e9, 1., 1. / 64.).points(), vec![1e9]);
+        assert_eq!(sweep(0., f32::INFINITY, 1.).point_count(), usize::MAX);
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct PrintCallsSpec {
-    pub target: Target,
-    pub length: f32,
-    pub offset: f32,
-    pub step: f32,
+    pub x: Sweep,
+    /// If present, every combination of the `x` and `y` values is tried, giving a grid of calls.
+    pub y: Option<Sweep>,
+    /// `None` writes each call as an `(x, performance)` tuple, as PrintCalls did before there were
+    /// formats to pick from, and writes grids as `Ron`.
+    pub format: Option<OutputFormat>,
+    /// Where to write an SVG chart of the calls, if anywhere.
+    pub chart_path: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchSpec {
+    pub sample_count: SampleCount,
+}
+
+/// A recorded event log, to run against the shelf instead of generating events from the specs.
+#[derive(Clone, Debug)]
+pub struct ReplaySpec {
+    pub entries: Vec<crate::event_log::LogEntry>,
 }
 
 #[derive(Clone, Debug)]
@@ -154,6 +720,24 @@ pub enum BasicMode {
     Run,
     Search(SearchSpec),
     PrintCalls(PrintCallsSpec),
+    Batch(BatchSpec),
+    Replay(ReplaySpec),
+}
+
+/// Which foods can be eaten instead of one that has run out. If none of the foods on the shelf
+/// are allowed, that counts as starving.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+pub enum SubstitutionPolicy {
+    /// Any other food, favouring ones with a similar serving size.
+    #[default]
+    ServingSize,
+    /// Only foods that share a category with the one that ran out, favouring ones with a similar
+    /// serving size.
+    SameCategory,
+    /// Only the foods in the `substitutes` list of the one that ran out, earliest first.
+    Ranked,
+    /// Any other food, picked at random.
+    Random,
 }
 
 #[derive(Clone, Debug)]
@@ -162,8 +746,13 @@ pub struct BasicExtras {
     pub food_types: FoodTypes,
     pub initial_event_source_specs: Vec1<EventSourceSpec>,
     pub repeated_event_source_specs: Vec1<EventSourceSpec>,
+    pub objective: Objective,
+    pub recipes: Vec<food::Recipe>,
+    pub substitution_policy: SubstitutionPolicy,
 }
 
+// There is only ever one of these around per run, so the size difference doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Default)]
 pub enum Mode {
     #[default]
@@ -182,6 +771,8 @@ pub mod food {
     /// For cases where we want somthing the same size as grams but which is not semantically grams.
     pub type GramsSizedType = Grams;
     pub type NonZeroGramsSizedType = NonZeroGrams;
+    /// Grams added up across a shelf, a day or a whole run, which can go well past 64k.
+    pub type TotalGrams = u32;
 
     pub type Servings = GramsSizedType;
 
@@ -189,9 +780,19 @@ pub mod food {
 
     pub type Key = String;
 
-    #[derive(Clone, Debug, serde::Deserialize)]
+    #[derive(Clone, Debug, Default, serde::Deserialize)]
     pub struct Option {
         pub grams: Grams,
+        #[serde(default)]
+        pub price: Money,
+        /// How many days an unopened item lasts on the shelf. `None` means it never goes bad.
+        /// If not given, this is filled in from the `Type` this option belongs to.
+        #[serde(default)]
+        pub shelf_life: core::option::Option<DayCount>,
+        /// How many days an item lasts on the shelf after it has been opened.
+        /// If not given, this is filled in from the `Type` this option belongs to.
+        #[serde(default)]
+        pub opened_shelf_life: core::option::Option<DayCount>,
     }
 
     pub const fn default_serving() -> NonZeroGrams {
@@ -201,12 +802,57 @@ pub mod food {
         }
     }
 
+    pub const fn default_weight() -> f32 {
+        1.
+    }
+
     #[derive(Clone, Debug, serde::Deserialize)]
     pub struct Type {
         pub key: Key,
         pub options: Vec1<Option>,
         #[serde(default = "default_serving")]
         pub serving: NonZeroGrams,
+        /// The default shelf life for all the options.
+        #[serde(default)]
+        pub shelf_life: core::option::Option<DayCount>,
+        /// The default opened shelf life for all the options.
+        #[serde(default)]
+        pub opened_shelf_life: core::option::Option<DayCount>,
+        /// Tags like "Dairy" or "Vegetable", for the `SameCategory` substitution policy.
+        #[serde(default)]
+        pub categories: Vec<String>,
+        /// The keys of the foods to eat instead of this one if it runs out, best first, for the
+        /// `Ranked` substitution policy.
+        #[serde(default)]
+        pub substitutes: Vec<Key>,
+        /// How often this is picked, relative to the other food types, by hunger sources that
+        /// pick foods at random. Hunger sources can override this.
+        #[serde(default = "default_weight")]
+        pub weight: f32,
+    }
+
+    impl Type {
+        /// A food type that can only be bought as `option`, with everything else as if it was
+        /// left out of the config file.
+        pub fn new(key: impl Into<Key>, option: Option) -> Self {
+            Self {
+                key: key.into(),
+                options: Vec1::new(option),
+                serving: default_serving(),
+                shelf_life: None,
+                opened_shelf_life: None,
+                categories: Vec::new(),
+                substitutes: Vec::new(),
+                weight: default_weight(),
+            }
+        }
+    }
+
+    /// Several foods that are eaten together. Either all of the ingredients are eaten, or none are.
+    #[derive(Clone, Debug, serde::Deserialize)]
+    pub struct Recipe {
+        pub name: String,
+        pub ingredients: Vec1<(Key, Grams)>,
     }
 }
 
@@ -214,12 +860,32 @@ pub mod food {
 pub struct Spec {
     pub mode: Mode,
     pub seed: Option<Seed>,
+    pub worker_count: crate::parallel::WorkerCount,
     pub day_count_min: DayCount,
     pub day_count_one_past_max: DayCount,
     pub hide_summary: bool,
     pub show_grams: bool,
     pub show_items: bool,
     pub show_step_by_step: bool,
+    /// Where to write an SVG chart of the stock levels over time, if anywhere.
+    pub stock_chart_path: Option<String>,
+    /// Where to write the stock of each food type at the end of each day, if anywhere.
+    pub stock_series_path: Option<String>,
+    pub stock_series_format: OutputFormat,
+    /// Where to write an NDJSON log of every step of the simulation, if anywhere.
+    pub event_log_path: Option<String>,
+}
+
+impl Spec {
+    /// A copy that does not write any files, for runs that are only one of many.
+    pub fn without_files(&self) -> Self {
+        Self {
+            stock_chart_path: None,
+            stock_series_path: None,
+            event_log_path: None,
+            ..self.clone()
+        }
+    }
 }
 
 pub type Res<A> = Result<A, Box<dyn std::error::Error>>;
\ No newline at end of file
@@ -29,9 +29,40 @@ pub fn range(xs: &mut Xs, range: Range<u32>) -> u32 {
 
 const SCALE: u32 = 1 << f32::MANTISSA_DIGITS;
 
-#[allow(unused)]
-fn zero_to_one(xs: &mut Xs) -> f32 {
-    (range(xs, 0..SCALE + 1) as f32 / SCALE as f32) - 1.
+pub fn zero_to_one(xs: &mut Xs) -> f32 {
+    range(xs, 0..SCALE + 1) as f32 / SCALE as f32
+}
+
+/// Picks an index into `weights`, each with a chance in proportion to its weight.
+/// If all the weights are equal, this is the same as `range(xs, 0..weights.len())`, down to the
+/// random numbers used, so equal weights give the same results as not weighting at all.
+/// Returns `None` if there is nothing that can be picked: if `weights` is empty, or the weights
+/// add up to 0 or less. No random numbers are used in that case.
+pub fn weighted_index(xs: &mut Xs, weights: &[f32]) -> Option<usize> {
+    let total: f32 = weights.iter().sum();
+
+    if total <= 0. || !total.is_finite() {
+        return None;
+    }
+
+    let all_equal = weights.windows(2).all(|pair| pair[0] == pair[1]);
+
+    if all_equal {
+        return Some(range(xs, 0..weights.len() as u32) as usize);
+    }
+
+    let mut remaining = zero_to_one(xs) * total;
+
+    for (i, &weight) in weights.iter().enumerate() {
+        if remaining < weight {
+            return Some(i);
+        }
+
+        remaining -= weight;
+    }
+
+    // Rounding, or rolling exactly the total, can take us past the end.
+    weights.iter().rposition(|&weight| weight > 0.)
 }
 
 #[allow(unused)]
@@ -115,6 +146,23 @@ pub fn new_seed(xs: &mut Xs) -> Seed {
     ]
 }
 
+/// The given seed, followed by `count - 1` more seeds. Each seed is made by `new_seed` from a fresh
+/// RNG seeded with the previous seed, rather than from the RNG that a run with the previous seed
+/// ended up with. That way all the seeds are known before any runs happen, so the runs can be
+/// spread across workers, and Search can use the same seeds for every evaluation.
+pub fn chained_seeds(seed: Seed, count: usize) -> Vec<Seed> {
+    let mut seeds = Vec::with_capacity(count);
+
+    let mut current = seed;
+    for _ in 0..count {
+        seeds.push(current);
+
+        current = new_seed(&mut from_seed(current));
+    }
+
+    seeds
+}
+
 pub fn from_seed(mut seed: Seed) -> Xs {
     // 0 doesn't work as a seed, so use this one instead.
     if seed == [0; 16] {
@@ -141,3 +189,39 @@ pub fn from_seed(mut seed: Seed) -> Xs {
         wrap!(12, 13, 14, 15),
     ]
 }
+
+#[cfg(test)]
+mod weighted_index_works {
+    use super::*;
+
+    #[test]
+    fn like_range_when_the_weights_are_equal() {
+        let mut a = from_seed([3; 16]);
+        let mut b = from_seed([3; 16]);
+
+        for _ in 0..100 {
+            assert_eq!(weighted_index(&mut a, &[2., 2., 2.]), Some(range(&mut b, 0..3) as usize));
+        }
+    }
+
+    #[test]
+    fn in_proportion_to_the_weights() {
+        let mut xs = from_seed([3; 16]);
+
+        let mut counts = [0; 3];
+        for _ in 0..10_000 {
+            counts[weighted_index(&mut xs, &[3., 0., 1.]).unwrap()] += 1;
+        }
+
+        assert_eq!(counts[1], 0);
+        assert!(counts[0] > counts[2] * 2, "{counts:?}");
+    }
+
+    #[test]
+    fn on_nothing_to_pick() {
+        let mut xs = from_seed([3; 16]);
+
+        assert_eq!(weighted_index(&mut xs, &[0., 0.]), None);
+        assert_eq!(weighted_index(&mut xs, &[]), None);
+    }
+}